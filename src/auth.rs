@@ -0,0 +1,168 @@
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use clap::ValueEnum;
+use http::HeaderValue;
+use serde::{Deserialize, Serialize};
+
+use crate::{oauth2::OAuth2, secret::Secret, sigv4::AwsSigV4};
+
+/// The kind of authentication to perform with the `--auth` credentials
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum AuthType {
+    #[default]
+    Basic,
+    Bearer,
+    Digest,
+    #[value(name = "aws-sigv4")]
+    AwsSigV4,
+}
+
+/// Credentials used to authenticate a request
+///
+/// Passwords and tokens may be secret references. When a password is omitted,
+/// the user is prompted for it at request time.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum Auth {
+    Basic {
+        username: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        password: Option<Secret>,
+    },
+
+    Bearer {
+        token: Secret,
+    },
+
+    Digest {
+        username: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        password: Option<Secret>,
+    },
+
+    OAuth2(OAuth2),
+
+    #[serde(rename = "aws-sigv4")]
+    AwsSigV4(AwsSigV4),
+}
+
+impl Auth {
+    /// Creates credentials from `--auth` input
+    ///
+    /// Basic and digest credentials are given as "user:pass" or "user", and
+    /// bearer credentials are given as the token itself.
+    pub fn from_input(auth_type: AuthType, input: &str) -> Result<Self> {
+        if input.is_empty() {
+            bail!("Credentials must not be empty");
+        }
+
+        let (username, password) = match input.split_once(':') {
+            Some((username, password)) => (username.to_string(), Some(password.into())),
+            None => (input.to_string(), None),
+        };
+
+        Ok(match auth_type {
+            AuthType::Basic => Self::Basic { username, password },
+            AuthType::Bearer => Self::Bearer {
+                token: input.into(),
+            },
+            AuthType::Digest => Self::Digest { username, password },
+            AuthType::AwsSigV4 => {
+                bail!("AWS credentials are read from the environment or a profile, not --auth")
+            }
+        })
+    }
+
+    /// Returns the Authorization header value for credentials that can be
+    /// sent up front
+    ///
+    /// Digest credentials return `None`, as they can only be sent in response
+    /// to a challenge from the server, as do AWS credentials, which sign the
+    /// final request. OAuth2 tokens are cached in `token_cache`, or in the
+    /// data home by default.
+    pub async fn header_value(&self, token_cache: Option<&Path>) -> Result<Option<HeaderValue>> {
+        let value = match self {
+            Self::Basic { username, password } => {
+                let password = password_or_prompt(username, password.as_ref()).await?;
+                let credentials = STANDARD.encode(format!("{}:{}", username, password));
+                format!("Basic {}", credentials)
+            }
+
+            Self::Bearer { token } => format!("Bearer {}", token.resolve().await?),
+
+            Self::Digest { .. } | Self::AwsSigV4(_) => return Ok(None),
+
+            Self::OAuth2(oauth2) => format!("Bearer {}", oauth2.access_token(token_cache).await?),
+        };
+
+        let mut value = HeaderValue::from_str(&value).context("valid authorization header")?;
+        value.set_sensitive(true);
+
+        Ok(Some(value))
+    }
+}
+
+/// Resolves the given password, or prompts for one without echoing input
+pub async fn password_or_prompt(username: &str, password: Option<&Secret>) -> Result<String> {
+    match password {
+        Some(password) => password.resolve().await,
+        None => rpassword::prompt_password(format!("Password for {}: ", username))
+            .context("read password"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_auth_input() {
+        assert_eq!(
+            Auth::from_input(AuthType::Basic, "user:pa:ss").unwrap(),
+            Auth::Basic {
+                username: "user".to_string(),
+                password: Some("pa:ss".into()),
+            }
+        );
+        assert_eq!(
+            Auth::from_input(AuthType::Bearer, "a:b").unwrap(),
+            Auth::Bearer {
+                token: "a:b".into()
+            }
+        );
+        assert!(Auth::from_input(AuthType::Basic, "").is_err());
+        assert!(Auth::from_input(AuthType::AwsSigV4, "key").is_err());
+    }
+
+    #[test]
+    fn leave_missing_password_to_prompt() {
+        // The password is prompted for at request time, not when parsing.
+        assert_eq!(
+            Auth::from_input(AuthType::Digest, "user").unwrap(),
+            Auth::Digest {
+                username: "user".to_string(),
+                password: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn encode_basic_credentials() {
+        let auth = Auth::from_input(AuthType::Basic, "user:pass").unwrap();
+        let value = auth.header_value(None).await.unwrap().unwrap();
+
+        assert_eq!(value, "Basic dXNlcjpwYXNz");
+        assert!(value.is_sensitive());
+        assert_eq!(
+            Auth::from_input(AuthType::Digest, "user:pass")
+                .unwrap()
+                .header_value(None)
+                .await
+                .unwrap(),
+            None
+        );
+    }
+}
@@ -1,27 +1,77 @@
-use std::str::FromStr;
-
-use anyhow::{bail, Result};
-use clap::Parser;
-use colored::Colorize;
-use http::{Method, Version};
+use std::{
+    collections::BTreeMap, env, error::Error as _, ffi::OsStr, path::PathBuf, process::ExitCode,
+    str::FromStr,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::SecondsFormat;
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::{ArgValueCompleter, CompleteEnv, CompletionCandidate};
+use colored::{ColoredString, Colorize};
+use http::{HeaderValue, Method};
 use reqwest::Response;
 
-use crate::config::Config;
+use crate::auth::{Auth, AuthType};
+use crate::config::{Config, LayeredConfig};
+use crate::error::Error;
+use crate::format::Format;
+use crate::hsts::HstsCache;
+use crate::netrc::{Entry, Netrc, NetrcMode};
 use crate::parser::ParsedRequest;
-use crate::request_builder::RequestBuilder;
+use crate::request_builder::{InputOptions, RequestBuilder};
+use crate::session::{self, Import, SessionStore};
+use crate::signing::SigningProfileRef;
+use crate::sigv4::AwsSigV4;
+use crate::template::Renderer;
+use crate::transport::{HttpVersion, Tls, Transport};
+use crate::validate::Problem;
 
 #[derive(Parser)]
-#[command(version, about, long_about = None)]
-pub struct CLI {
-    url: String,
+#[command(
+    version,
+    about,
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true,
+    after_long_help = EXIT_STATUS
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    // Indexes are explicit as adding the completer moves the argument.
+    #[arg(
+        required = true,
+        index = 1,
+        help = "URL to request; add a trailing slash to request a host or alias named like a subcommand, e.g. config/"
+    )]
+    url: Option<String>,
+
+    #[arg(
+        index = 2,
+        help = "Query parameters as name==value, headers as Name:value, Name; for an empty value or Name: to remove one (except Host and Accept), and body values as path=value or path:=json",
+        long_help = "Query parameters as name==value, headers as Name:value, Name; for an empty value or Name: to remove one (except Host and Accept), and body values as path=value or path:=json
+
+Host and Accept can't be removed, as the HTTP client always sends them, so a request without them can't be made with get.
+
+A body value ending in ::str, ::int, ::float, ::bool or ::list is sent as that type, e.g. count=5::int, or ids=1,2::list for [\"1\",\"2\"]. This changes the meaning of a value that already ends in one of these suffixes; append ::str to send it as is, e.g. name=a::list::str"
+    )]
     components: Vec<String>,
 
     #[arg(
         long,
+        global = true,
         help = "Path to the config file [default: $XDG_CONFIG_HOME/get/config.json]"
     )]
     config: Option<String>,
 
+    #[arg(
+        short,
+        long,
+        help = "Environment in the config file whose variables fill {{name}} placeholders"
+    )]
+    env: Option<String>,
+
     #[arg(short, long, help = "Data to send in the request body")]
     data: Option<String>,
 
@@ -32,42 +82,370 @@ pub struct CLI {
     )]
     method: Option<String>,
 
+    #[arg(
+        short,
+        long,
+        help = "Credentials to authenticate with, e.g. user:pass, or a token for bearer auth"
+    )]
+    auth: Option<String>,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "Type of authentication to use [default: basic]"
+    )]
+    auth_type: Option<AuthType>,
+
+    #[arg(
+        long,
+        requires = "auth_type",
+        help = "AWS region to sign requests for with aws-sigv4 auth"
+    )]
+    aws_region: Option<String>,
+
+    #[arg(
+        long,
+        requires = "auth_type",
+        help = "AWS service to sign requests for with aws-sigv4 auth"
+    )]
+    aws_service: Option<String>,
+
+    #[arg(
+        long,
+        requires = "auth_type",
+        help = "AWS credentials profile to use with aws-sigv4 auth"
+    )]
+    aws_profile: Option<String>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t,
+        help = "Whether to use credentials from the .netrc file"
+    )]
+    netrc: NetrcMode,
+
+    #[arg(long, help = "Path to the .netrc file [default: $NETRC or ~/.netrc]")]
+    netrc_file: Option<String>,
+
+    #[arg(
+        long,
+        help = "Name of a signing profile in the config file to sign the request with"
+    )]
+    signing_profile: Option<String>,
+
+    #[arg(long, help = "Do not prepend the session's base path to the URL path")]
+    no_base_path: bool,
+
+    #[arg(
+        long,
+        help = "Do not upgrade hosts to HTTPS or record their Strict-Transport-Security policies"
+    )]
+    no_hsts: bool,
+
     #[arg(long, help = "Use HTTP, regardless of the URL scheme or session")]
     http: bool,
 
     #[arg(long, help = "Use HTTPS, regardless of the URL scheme or session")]
     https: bool,
 
+    #[arg(
+        long,
+        help = "Send body values and ::list items that look like numbers or booleans as such, not strings"
+    )]
+    infer_types: bool,
+
     #[arg(short, long, help = "Print verbose output")]
     verbose: bool,
 
+    #[arg(
+        long,
+        help = "Print credentials and secret header values in verbose output"
+    )]
+    show_secrets: bool,
+
+    #[arg(long, help = "Exit with an error for 3xx, 4xx and 5xx statuses")]
+    check_status: bool,
+
     #[arg(short = 'H', long, help = "Do not print response headers")]
     no_headers: bool,
 
     #[arg(short = 'B', long, help = "Do not print response body")]
     no_body: bool,
 
+    #[arg(long, help = "Maximum number of redirects to follow [default: 10]")]
+    max_redirects: Option<usize>,
+
+    #[arg(long, help = "Timeout for the whole request, in seconds")]
+    timeout: Option<f64>,
+
+    #[arg(long, help = "Timeout for connecting to the server, in seconds")]
+    connect_timeout: Option<f64>,
+
+    #[arg(long, value_enum, help = "HTTP version to use")]
+    http_version: Option<HttpVersion>,
+
+    #[arg(long, help = "URL of a proxy to send the request through")]
+    proxy: Option<String>,
+
+    #[arg(short = 'k', long, help = "Do not verify the server's TLS certificate")]
+    insecure: bool,
+
     #[arg(
         long,
-        help = "Maximum number of redirects to follow",
-        default_value = "10"
+        conflicts_with = "insecure",
+        help = "Verify the server's TLS certificate, even if the session or config disables it"
     )]
-    max_redirects: usize,
+    no_insecure: bool,
+
+    #[arg(long, help = "Path to a PEM file of CA certificates to trust")]
+    ca_cert: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List the host aliases from the config file
+    Aliases,
+
+    /// Inspect the config
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+
+    /// Manage the cache of hosts that require HTTPS
+    Hsts {
+        #[command(subcommand)]
+        command: HstsCommand,
+    },
+
+    /// Manage the session store
+    Session {
+        #[command(subcommand)]
+        command: SessionCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum SessionCommand {
+    /// Encrypt the session store in place, with the key file named by
+    /// GET_SESSION_KEY_FILE or a passphrase from GET_SESSION_PASSPHRASE or a
+    /// prompt
+    Encrypt,
+
+    /// Decrypt the session store in place
+    Decrypt,
+
+    /// Write the sessions for the given authorities to a file that can be
+    /// imported elsewhere
+    Export {
+        #[arg(required = true)]
+        authorities: Vec<String>,
+
+        #[arg(
+            long,
+            help = "Replace secrets with environment variable references, and remove secret query parameters"
+        )]
+        redact: bool,
+
+        #[arg(
+            short,
+            long,
+            help = "File to write, in the format of its extension, instead of stdout"
+        )]
+        output: Option<PathBuf>,
+    },
+
+    /// Add the sessions in a file to the session store, failing if any
+    /// authority already has a different session
+    Import {
+        file: PathBuf,
+
+        #[arg(long, help = "Add settings that aren't set yet, keeping existing ones")]
+        merge: bool,
+
+        #[arg(
+            long,
+            conflicts_with = "merge",
+            help = "Replace existing sessions with the imported ones"
+        )]
+        replace: bool,
+    },
 }
 
-pub async fn run() -> Result<()> {
-    let cli = CLI::parse();
+#[derive(Subcommand)]
+enum HstsCommand {
+    /// List the hosts that require HTTPS
+    List,
 
-    let config = if let Some(config) = cli.config {
-        Config::load_from_path(&config).await?
-    } else {
-        Config::load().await?
+    /// Forget the policies of the given hosts, or of every host
+    Clear { hosts: Vec<String> },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Print the effective config, merged from each config file and GET_*
+    /// environment variable
+    Show {
+        #[arg(long, help = "Show where each value was set")]
+        origin: bool,
+    },
+
+    /// Validate the config files and the session store
+    Check,
+
+    /// Convert the config file or session store to another format, keeping
+    /// the original as a backup
+    Migrate {
+        #[arg(value_enum, help = "Format to convert to")]
+        to: Format,
+
+        #[arg(long, help = "Convert the session store instead of the config file")]
+        sessions: bool,
+    },
+}
+
+const EXIT_STATUS: &str = "\
+Exit status:
+  0   Success
+  1   Any other error
+  2   Invalid arguments or request components
+  3   Invalid config, or an OAuth2 token couldn't be fetched
+  4   The session store was invalid, or couldn't be read or written
+  5   Connecting failed, e.g. a DNS lookup or refusal
+  6   TLS failed, e.g. an untrusted certificate
+  7   The request timed out
+  8   Too many redirects
+  13  A 3xx status, with --check-status
+  14  A 4xx status, with --check-status
+  15  A 5xx status, with --check-status";
+
+/// Runs the `get` command with the process's arguments, printing any error
+/// and returning the exit status documented in `--help`
+pub async fn run() -> ExitCode {
+    match run_command().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            report_error(&err);
+            ExitCode::from(err.exit_code())
+        }
+    }
+}
+
+async fn run_command() -> Result<(), Error> {
+    // Completers are synchronous, so aliases are loaded up front, from the
+    // config file given on the command line being completed.
+    let aliases = match env::var_os("COMPLETE") {
+        Some(_) => Config::load_layered(completion_config(env::args()).as_deref())
+            .await
+            .map(|layered| layered.config.aliases)
+            .unwrap_or_default(),
+        None => BTreeMap::new(),
+    };
+
+    CompleteEnv::with_factory(move || {
+        let aliases = aliases.clone();
+        Cli::command().mut_arg("url", |arg| {
+            arg.add(ArgValueCompleter::new(move |current: &OsStr| {
+                complete_url(current, &aliases)
+            }))
+        })
+    })
+    .complete();
+
+    let mut cli = Cli::parse();
+
+    match cli.command.take() {
+        // These work on the files themselves, which may not load.
+        Some(Command::Config {
+            command: ConfigCommand::Check,
+        }) => check(cli.config.as_deref()).await.map_err(Error::Config),
+        Some(Command::Config {
+            command: ConfigCommand::Migrate { to, sessions },
+        }) => {
+            let result = migrate(cli.config.as_deref(), to, sessions).await;
+            result.map_err(if sessions {
+                Error::Session
+            } else {
+                Error::Config
+            })
+        }
+
+        Some(Command::Config {
+            command: ConfigCommand::Show { origin },
+        }) => {
+            let layered = load_config(cli.config.as_deref()).await?;
+            Ok(print_config(&layered, origin)?)
+        }
+        Some(Command::Aliases) => {
+            print_aliases(&load_config(cli.config.as_deref()).await?.config);
+            Ok(())
+        }
+        Some(Command::Hsts { command }) => Ok(hsts(command).await?),
+        Some(Command::Session { command }) => session(command).await.map_err(Error::Session),
+        None => request(cli).await,
+    }
+}
+
+/// Prints problems to stderr
+fn report(problems: &[Problem]) {
+    for problem in problems {
+        eprintln!("{}", problem);
+    }
+}
+
+/// Prints an error and its causes to stderr
+fn report_error(err: &Error) {
+    eprintln!("{} {}", "error:".red(), err);
+
+    let mut source = err.source();
+    while let Some(cause) = source {
+        eprintln!("  {} {}", "caused by:".dimmed(), cause);
+        source = cause.source();
+    }
+}
+
+/// Loads the config, reporting any problems that don't prevent using it
+async fn load_config(path: Option<&str>) -> Result<LayeredConfig, Error> {
+    let layered = Config::load_layered(path).await?;
+    report(&layered.warnings);
+    report(&shadowed_aliases(&layered.config));
+    Ok(layered)
+}
+
+/// Sends the request described by the arguments and prints the response
+async fn request(cli: Cli) -> Result<(), Error> {
+    let config = load_config(cli.config.as_deref()).await?.config;
+
+    let url = cli.url.context("URL given").map_err(Error::Usage)?;
+
+    // Without an environment, {{name}} is left as is, as it may be meant
+    // literally.
+    let variables = match &cli.env {
+        Some(name) => Some(config.environment(name).map_err(Error::Usage)?),
+        None => None,
     };
 
-    let parsed_request = ParsedRequest::from_inputs(&cli.components)?;
+    let mut renderer = match variables {
+        Some(variables) => Renderer::new(variables),
+        None => Renderer::without_variables(),
+    };
+    let url = renderer.render(&url);
+    let parsed_request =
+        ParsedRequest::from_inputs(&cli.components, &mut renderer).map_err(Error::Usage)?;
+
+    renderer
+        .finish()
+        .with_context(|| match &cli.env {
+            Some(name) => format!("render request for environment {}", name),
+            None => "render request".to_string(),
+        })
+        .map_err(Error::Usage)?;
 
     if cli.http && cli.https {
-        bail!("Cannot specify both --http and --https");
+        return Err(Error::Usage(anyhow!(
+            "Cannot specify both --http and --https"
+        )));
     }
 
     let scheme = if cli.http {
@@ -78,59 +456,417 @@ pub async fn run() -> Result<()> {
         None
     };
 
-    let mut req = RequestBuilder::from_input(scheme, &cli.url, &config)
-        .await?
-        .version(Version::default())
+    let options = InputOptions {
+        scheme: scheme.map(String::from),
+        base_path: !cli.no_base_path,
+        hsts: !cli.no_hsts,
+    };
+
+    let mut req = RequestBuilder::from_input(&url, &config, variables, &options).await?;
+    report(&req.take_warnings());
+
+    let insecure = match (cli.insecure, cli.no_insecure) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
+    };
+
+    let tls = if insecure.is_some() || cli.ca_cert.is_some() {
+        Some(Tls {
+            insecure,
+            ca_cert: cli.ca_cert,
+        })
+    } else {
+        None
+    };
+
+    let transport = Transport {
+        timeout: cli.timeout,
+        connect_timeout: cli.connect_timeout,
+        max_redirects: cli.max_redirects,
+        http_version: cli.http_version,
+        proxy: cli.proxy,
+        tls,
+    };
+
+    let aws_options =
+        cli.aws_region.is_some() || cli.aws_service.is_some() || cli.aws_profile.is_some();
+    if aws_options && cli.auth_type != Some(AuthType::AwsSigV4) {
+        return Err(Error::Usage(anyhow!(
+            "--aws-region, --aws-service and --aws-profile require --auth-type aws-sigv4"
+        )));
+    }
+
+    let auth = match (&cli.auth, cli.auth_type) {
+        (_, Some(AuthType::AwsSigV4)) => Some(Auth::AwsSigV4(AwsSigV4 {
+            region: cli.aws_region,
+            service: cli.aws_service,
+            profile: cli.aws_profile,
+        })),
+        (Some(auth), auth_type) => {
+            Some(Auth::from_input(auth_type.unwrap_or_default(), auth).map_err(Error::Usage)?)
+        }
+        (None, Some(_)) => return Err(Error::Usage(anyhow!("--auth-type requires --auth"))),
+        (None, None) => None,
+    };
+
+    if let Some(auth) = auth {
+        req = req.auth(auth);
+    }
+
+    if let Some(name) = cli.signing_profile {
+        let profile = SigningProfileRef::Named(name)
+            .resolve(&config.signing_profiles)
+            .map_err(Error::Config)?;
+        req = req.signing(profile);
+    }
+
+    let mut req = req
+        .transport(transport)
         .add_query(&parsed_request.query)
         .merge_headers(parsed_request.headers)?
-        .add_data(&parsed_request.body, cli.data.as_ref().map(String::as_ref))?;
+        .remove_headers(&parsed_request.removed_headers)
+        .map_err(Error::Usage)?
+        .infer_types(cli.infer_types)
+        .add_data(&parsed_request.body, cli.data.as_ref().map(String::as_ref))
+        .map_err(Error::Usage)?;
+
+    // Credentials from .netrc are only used when no others are configured.
+    if cli.netrc != NetrcMode::Ignore && !req.has_authorization() {
+        let hostname = req.url().hostname.clone().context("hostname parsed")?;
+        let netrc = Netrc::load(cli.netrc_file.as_deref())
+            .await
+            .map_err(Error::Usage)?;
+
+        match netrc.find(&hostname).and_then(Entry::auth) {
+            Some(auth) => req = req.auth(auth),
+
+            None if cli.netrc == NetrcMode::Required => {
+                return Err(Error::Config(anyhow!(
+                    "No .netrc credentials found for {}",
+                    hostname
+                )));
+            }
+
+            None => {}
+        }
+    }
 
     let method = if let Some(method) = cli.method {
-        Method::from_str(&method)?
-    } else if req.body.is_some() {
+        Method::from_str(&method)
+            .with_context(|| format!("Invalid method {}", method))
+            .map_err(Error::Usage)?
+    } else if req.body().is_some() {
         Method::POST
     } else {
         Method::GET
     };
 
+    // Inferred body value types are only worth mentioning in verbose output.
+    let warnings = req.take_warnings();
+
     if cli.verbose {
-        print_request(&method, &req)?;
+        req.authorize().await?;
+        report(&warnings);
+        print_request(&method, &req, cli.show_secrets)?;
         println!();
     }
 
-    let response = req.send(method, cli.max_redirects).await?;
+    let response = req.send(method).await?;
+    report(&req.take_warnings());
+    let status = response.status();
 
     print_response(response, !cli.no_headers, !cli.no_body).await?;
 
+    if cli.check_status
+        && (status.is_redirection() || status.is_client_error() || status.is_server_error())
+    {
+        return Err(Error::Status(status));
+    }
+
+    Ok(())
+}
+
+fn print_aliases(config: &Config) {
+    let width = config.aliases.keys().map(String::len).max().unwrap_or(0);
+
+    for (name, url) in &config.aliases {
+        println!("{:width$}  {}", name.cyan(), url);
+    }
+}
+
+fn print_config(layered: &LayeredConfig, origin: bool) -> Result<()> {
+    if !origin {
+        println!("{}", serde_json::to_string_pretty(&layered.config)?);
+        return Ok(());
+    }
+
+    let entries = layered.entries()?;
+    let width = entries
+        .iter()
+        .map(|(path, _, _)| path.len())
+        .max()
+        .unwrap_or(0);
+
+    for (path, value, origin) in entries {
+        println!(
+            "{:width$} = {}  {}",
+            path.cyan(),
+            value,
+            format!("({})", origin).dimmed()
+        );
+    }
+
+    Ok(())
+}
+
+async fn check(config: Option<&str>) -> Result<()> {
+    let mut warnings = vec![];
+    let mut files = vec![];
+
+    for path in Config::user_files(config, &mut warnings).await? {
+        let problems = Config::check_file(&path).await;
+        files.push((path, problems));
+    }
+
+    if let Some(path) = Config::project_file(&mut warnings).await? {
+        let problems = Config::check_project_file(&path, config).await;
+        files.push((path, problems));
+    }
+
+    files.push(SessionStore::check().await?);
+
+    report(&warnings);
+    let mut errors = 0;
+
+    for (path, problems) in &files {
+        if problems.is_empty() {
+            println!("{} {}", "ok:".green(), path.display());
+        }
+
+        report(problems);
+        errors += problems.iter().filter(|problem| problem.is_error()).count();
+    }
+
+    if errors > 0 {
+        bail!("Found {} error(s)", errors);
+    }
+
+    Ok(())
+}
+
+async fn migrate(config: Option<&str>, to: Format, sessions: bool) -> Result<()> {
+    let path = if sessions {
+        SessionStore::migrate(to).await?
+    } else {
+        Config::migrate(config, to).await?
+    };
+
+    println!("Wrote {}", path.display());
+
+    Ok(())
+}
+
+async fn hsts(command: HstsCommand) -> Result<()> {
+    let mut cache = HstsCache::load().await?;
+
+    match command {
+        HstsCommand::List => {
+            let width = cache.policies().keys().map(String::len).max().unwrap_or(0);
+
+            for (host, policy) in cache.policies() {
+                println!(
+                    "{:width$}  expires {}{}",
+                    host.cyan(),
+                    policy.expiry().to_rfc3339_opts(SecondsFormat::Secs, true),
+                    if policy.include_subdomains {
+                        ", includes subdomains"
+                    } else {
+                        ""
+                    }
+                );
+            }
+        }
+
+        HstsCommand::Clear { hosts } if hosts.is_empty() => {
+            cache.clear();
+            cache.save().await?;
+        }
+
+        HstsCommand::Clear { hosts } => {
+            for host in hosts {
+                if !cache.remove(&host) {
+                    eprintln!("No HSTS policy for {}", host);
+                }
+            }
+
+            cache.save().await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn session(command: SessionCommand) -> Result<()> {
+    match command {
+        SessionCommand::Encrypt => {
+            let path = SessionStore::encrypt().await?;
+            println!("Encrypted {}", path.display());
+        }
+
+        SessionCommand::Decrypt => {
+            let path = SessionStore::decrypt().await?;
+            println!("Decrypted {}", path.display());
+        }
+
+        SessionCommand::Export {
+            authorities,
+            redact,
+            output,
+        } => {
+            let mut warnings = vec![];
+            let exported = SessionStore::export(&authorities, redact, &mut warnings).await;
+            report(&warnings);
+            let (store, changes) = exported?;
+
+            for change in &changes {
+                eprintln!("{}", change);
+            }
+
+            match output {
+                // Unredacted exports hold credentials, so only the user may
+                // read them.
+                Some(path) => {
+                    let contents = Format::from_path(&path).serialize(&store)?;
+                    session::write_private(&path, contents.as_bytes()).await?;
+                    println!("Wrote {}", path.display());
+                }
+                None => print!("{}", Format::Json.serialize(&store)?),
+            }
+        }
+
+        SessionCommand::Import {
+            file,
+            merge,
+            replace,
+        } => {
+            let mode = match (merge, replace) {
+                (true, _) => Import::Merge,
+                (_, true) => Import::Replace,
+                _ => Import::New,
+            };
+
+            let mut warnings = vec![];
+            let imported = SessionStore::import(&file, mode, &mut warnings).await;
+            report(&warnings);
+            let conflicts = imported?;
+
+            for conflict in &conflicts {
+                eprintln!("{}", conflict);
+            }
+
+            if mode == Import::New && !conflicts.is_empty() {
+                bail!("Nothing imported; use --merge or --replace");
+            }
+
+            println!("Imported {}", file.display());
+        }
+    }
+
     Ok(())
 }
 
-fn print_request(method: &Method, req: &RequestBuilder) -> Result<()> {
-    let mut path = req.url.path.clone().unwrap_or(String::from("/"));
+/// Warns about aliases that can't be used alone, as a subcommand of the same
+/// name takes precedence
+fn shadowed_aliases(config: &Config) -> Vec<Problem> {
+    let command = Cli::command();
+
+    config
+        .aliases
+        .keys()
+        .filter(|name| command.find_subcommand(name).is_some())
+        .map(|name| {
+            Problem::Warning(format!(
+                "Alias {0} is shadowed by the {0} subcommand; use {0}/ instead",
+                name
+            ))
+        })
+        .collect()
+}
+
+/// Returns the last --config value in the command line being completed
+fn completion_config(args: impl Iterator<Item = String>) -> Option<String> {
+    let mut path = None;
+    let mut args = args.peekable();
 
-    if let Some(query) = &req.url.query {
-        path.push('?');
-        path.push_str(query);
+    while let Some(arg) = args.next() {
+        if let Some(value) = arg.strip_prefix("--config=") {
+            path = Some(value.to_string());
+        } else if arg == "--config" {
+            path = args.peek().cloned();
+        }
     }
 
+    path
+}
+
+/// Suggests aliases when completing the URL
+///
+/// Aliases are completed with a trailing slash, so that they aren't parsed
+/// as a subcommand of the same name.
+fn complete_url(current: &OsStr, aliases: &BTreeMap<String, String>) -> Vec<CompletionCandidate> {
+    let Some(current) = current.to_str() else {
+        return vec![];
+    };
+
+    aliases
+        .iter()
+        .filter(|(name, _)| name.starts_with(current))
+        .map(|(name, url)| {
+            CompletionCandidate::new(format!("{}/", name)).help(Some(url.clone().into()))
+        })
+        .collect()
+}
+
+/// Prints the request as sent, followed by its decoded query parameters in
+/// the `name==value` form they're given in
+fn print_request(method: &Method, req: &RequestBuilder, show_secrets: bool) -> Result<()> {
     println!(
         "{} {} {}",
         method.as_str().green(),
-        path.green(),
-        format!("{:?}", req.version).green(),
+        req.url().request_target().green(),
+        format!("{:?}", req.version()).green(),
     );
 
-    for (key, value) in req.headers.iter() {
-        println!("{} {}", format!("{}:", key).cyan(), value.to_str()?);
+    for (key, value) in req.url().query_pairs() {
+        println!("{}", format!("  {}=={}", key, value).dimmed());
     }
 
-    if let Some(body) = &req.body {
+    for (key, value) in req.headers().iter() {
+        println!(
+            "{} {}",
+            format!("{}:", key).cyan(),
+            display_header_value(value, show_secrets)?
+        );
+    }
+
+    if let Some(body) = req.body() {
         println!("\n{}", body);
     }
 
     Ok(())
 }
 
+/// Returns a header value for verbose output, redacting sensitive values,
+/// such as credentials and resolved secrets, unless `show_secrets` is set
+fn display_header_value(value: &HeaderValue, show_secrets: bool) -> Result<ColoredString> {
+    if value.is_sensitive() && !show_secrets {
+        Ok("<redacted>".dimmed())
+    } else {
+        Ok(value.to_str()?.normal())
+    }
+}
+
 async fn print_response(resp: Response, headers: bool, body: bool) -> Result<()> {
     if headers {
         println!(
@@ -155,3 +891,104 @@ async fn print_response(resp: Response, headers: bool, body: bool) -> Result<()>
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redact_sensitive_header_values() {
+        let mut value = HeaderValue::from_static("Bearer token");
+        assert_eq!(
+            &*display_header_value(&value, false).unwrap(),
+            "Bearer token"
+        );
+
+        value.set_sensitive(true);
+        assert_eq!(&*display_header_value(&value, false).unwrap(), "<redacted>");
+        assert_eq!(
+            &*display_header_value(&value, true).unwrap(),
+            "Bearer token"
+        );
+    }
+
+    #[test]
+    fn verify_cli() {
+        let aliases = BTreeMap::new();
+        Cli::command()
+            .mut_arg("url", |arg| {
+                arg.add(ArgValueCompleter::new(move |current: &OsStr| {
+                    complete_url(current, &aliases)
+                }))
+            })
+            .debug_assert();
+    }
+
+    #[test]
+    fn require_auth_type_for_aws_options() {
+        let Err(error) = Cli::try_parse_from(["get", "example.com", "--aws-region", "eu-west-1"])
+        else {
+            panic!("--aws-region was accepted without --auth-type");
+        };
+
+        assert_eq!(
+            error.kind(),
+            clap::error::ErrorKind::MissingRequiredArgument
+        );
+        assert!(Cli::try_parse_from([
+            "get",
+            "example.com",
+            "--auth-type",
+            "aws-sigv4",
+            "--aws-region",
+            "eu-west-1",
+        ])
+        .is_ok());
+    }
+
+    #[test]
+    fn find_completion_config() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            completion_config(args(&["get", "--", "get", "--config", "a.json", "gh"]).into_iter()),
+            Some("a.json".to_string())
+        );
+        assert_eq!(
+            completion_config(args(&["get", "--", "get", "--config=b.json", "g"]).into_iter()),
+            Some("b.json".to_string())
+        );
+        assert_eq!(
+            completion_config(args(&["get", "--", "get", "gh"]).into_iter()),
+            None
+        );
+    }
+
+    #[test]
+    fn complete_aliases() {
+        let aliases = BTreeMap::from([
+            ("gh".to_string(), "https://api.github.com".to_string()),
+            ("config".to_string(), "http://localhost:8080".to_string()),
+        ]);
+
+        let candidates: Vec<_> = complete_url(OsStr::new("c"), &aliases)
+            .iter()
+            .map(|candidate| candidate.get_value().to_string_lossy().to_string())
+            .collect();
+
+        assert_eq!(candidates, vec!["config/"]);
+    }
+
+    #[test]
+    fn warn_about_shadowed_aliases() {
+        let mut config = Config::default();
+        config
+            .aliases
+            .insert("config".to_string(), "http://a".to_string());
+        config
+            .aliases
+            .insert("gh".to_string(), "http://b".to_string());
+
+        assert_eq!(shadowed_aliases(&config).len(), 1);
+    }
+}
@@ -1,59 +1,364 @@
 use std::{
-    env,
-    io::ErrorKind,
+    collections::{BTreeMap, HashMap},
+    env, fmt,
     path::{Path, PathBuf},
 };
 
-use anyhow::{Context, Result};
-use serde::Deserialize;
-use tokio::{fs::File, io::AsyncReadExt};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tokio::fs;
 
-#[derive(Debug, Deserialize)]
+use crate::{
+    error::Error,
+    format::{self, Format},
+    secret::expand_home,
+    signing::{SigningPart, SigningProfile},
+    transport::Transport,
+    validate::{self, Problem},
+};
+
+/// Settings that apply to every request, merged from the config files and
+/// environment variables by `load`
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Config {
-    pub fallback_hostname: String,
-    pub http_hostnames: Vec<String>,
+    pub(crate) fallback_hostname: String,
+    /// Hostname patterns to use HTTP for by default, see
+    /// `host_pattern::matches`
+    pub(crate) http_hostnames: Vec<String>,
+
+    /// Hostname patterns to use HTTPS for, even if they match
+    /// `http_hostnames` or are private IP addresses
+    pub(crate) https_hostnames: Vec<String>,
+
+    pub(crate) signing_profiles: HashMap<String, SigningProfile>,
+    pub(crate) transport: Transport,
+
+    /// Short names for base URLs, expanded from the first path segment of
+    /// the URL input, e.g. `gh` for `https://api.github.com`
+    pub(crate) aliases: BTreeMap<String, String>,
+
+    /// Variables for `{{name}}` placeholders, by environment name
+    pub(crate) environments: BTreeMap<String, BTreeMap<String, String>>,
+
+    /// Directories whose project config file may set any key, rather than
+    /// only `PROJECT_KEYS`
+    pub(crate) trusted_projects: Vec<String>,
 }
 
 const FALLBACK_HOSTNAME: &str = "localhost";
 
-impl Config {
-    pub fn default() -> Self {
+/// The keys a project config file may set without its directory being
+/// trusted, as they can't run commands, weaken TLS, add a proxy, downgrade a
+/// host to HTTP or point a name at a host we have credentials for
+const PROJECT_KEYS: &[&str] = &["environments", "https_hostnames"];
+
+/// The prefix of environment variables that override config values
+const ENV_PREFIX: &str = "GET_";
+
+/// Where a config value was set
+#[derive(Clone, Debug, PartialEq)]
+pub enum Origin {
+    Default,
+    File(PathBuf),
+    Env(String),
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Default => write!(f, "default"),
+            Self::File(path) => write!(f, "{}", path.display()),
+            Self::Env(name) => write!(f, "${}", name),
+        }
+    }
+}
+
+/// The effective config, along with where each of its values was set
+#[non_exhaustive]
+pub struct LayeredConfig {
+    pub config: Config,
+
+    /// The origin of each value set by a layer, keyed by its dotted path,
+    /// e.g. "transport.timeout"
+    origins: BTreeMap<String, Origin>,
+
+    /// Problems found in the config files that don't prevent loading them,
+    /// e.g. unknown keys
+    pub warnings: Vec<Problem>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
         Config {
             fallback_hostname: FALLBACK_HOSTNAME.to_string(),
             http_hostnames: vec![FALLBACK_HOSTNAME.to_string()],
+            https_hostnames: vec![],
+            signing_profiles: HashMap::new(),
+            transport: Transport::default(),
+            aliases: BTreeMap::new(),
+            environments: BTreeMap::new(),
+            trusted_projects: vec![],
+        }
+    }
+}
+
+impl Config {
+    /// Returns the variables of the named environment
+    pub fn environment(&self, name: &str) -> Result<&BTreeMap<String, String>> {
+        self.environments
+            .get(name)
+            .with_context(|| format!("Environment {} is not configured", name))
+    }
+
+    /// Loads the effective config, as described for `get config show`
+    ///
+    /// Problems with the config files that don't prevent loading them, e.g.
+    /// unknown keys, are ignored.
+    pub async fn load() -> Result<Self, Error> {
+        Ok(Self::load_layered(None).await?.config)
+    }
+
+    /// Loads the effective config with the given config file in place of the
+    /// user config file, like `--config`
+    pub async fn load_from(path: &str) -> Result<Self, Error> {
+        Ok(Self::load_layered(Some(path)).await?.config)
+    }
+
+    /// The hostname to request when the URL has none, e.g. for `:8080/path`
+    pub fn fallback_hostname(&self) -> &str {
+        &self.fallback_hostname
+    }
+
+    /// Hostname patterns to use HTTP for by default
+    pub fn http_hostnames(&self) -> &[String] {
+        &self.http_hostnames
+    }
+
+    /// Hostname patterns to use HTTPS for, even if they match
+    /// `http_hostnames` or are private IP addresses
+    pub fn https_hostnames(&self) -> &[String] {
+        &self.https_hostnames
+    }
+
+    /// Short names for base URLs, by name
+    pub fn aliases(&self) -> &BTreeMap<String, String> {
+        &self.aliases
+    }
+
+    /// Variables for `{{name}}` placeholders, by environment name
+    pub fn environments(&self) -> &BTreeMap<String, BTreeMap<String, String>> {
+        &self.environments
+    }
+
+    /// Directories whose project config file may set any key
+    pub fn trusted_projects(&self) -> &[String] {
+        &self.trusted_projects
+    }
+
+    /// Loads and merges each layer of config
+    ///
+    /// In order of precedence, the layers are:
+    /// - `GET_*` environment variables, e.g. `GET_FALLBACK_HOSTNAME`, with
+    ///   `__` separating nested keys, e.g. `GET_TRANSPORT__TIMEOUT`
+    /// - The nearest `.get.json` or `.get/config.json` in the current
+    ///   directory or one of its parents, or the TOML or YAML equivalent
+    /// - The given config file, or the user config file if there is one
+    ///
+    /// Unless its directory is in `trusted_projects`, the project config file
+    /// may only set `PROJECT_KEYS`, as it may come from a cloned repository.
+    ///
+    /// Objects are merged key by key, while other values, and each signing
+    /// profile, are replaced as a whole.
+    pub(crate) async fn load_layered(path: Option<&str>) -> Result<LayeredConfig, Error> {
+        Self::read_layers(path).await.map_err(Error::Config)
+    }
+
+    async fn read_layers(path: Option<&str>) -> Result<LayeredConfig> {
+        let mut layers = vec![];
+        let mut warnings = vec![];
+
+        for path in Self::user_files(path, &mut warnings).await? {
+            layers.push(read_layer(path, &mut warnings).await?);
+        }
+
+        let env_layers = env_layers(env::vars())?;
+
+        if let Some(path) = Self::project_file(&mut warnings).await? {
+            let trusted = trusted_projects(layers.iter().chain(&env_layers));
+            let (origin, layer) = read_layer(path.clone(), &mut warnings).await?;
+
+            if let Some(message) = check_trust(&path, &layer, &trusted) {
+                bail!(message);
+            }
+
+            layers.push((origin, layer));
+        }
+
+        layers.extend(env_layers);
+
+        let mut merged = Value::Object(Map::new());
+        let mut origins = BTreeMap::new();
+
+        for (origin, layer) in layers {
+            merge(&mut merged, layer, "", &origin, &mut origins);
+        }
+
+        let config_file: ConfigFile = serde_json::from_value(merged).context("merge config")?;
+
+        Ok(LayeredConfig {
+            config: config_file.into(),
+            origins,
+            warnings,
+        })
+    }
+
+    /// Returns the given config file, or the user config file if it exists
+    ///
+    /// Files ignored because another format takes precedence are reported as
+    /// warnings.
+    pub(crate) async fn user_files(
+        path: Option<&str>,
+        warnings: &mut Vec<Problem>,
+    ) -> Result<Vec<PathBuf>> {
+        match path {
+            Some(path) => Ok(vec![PathBuf::from(path)]),
+            None => Ok(format::find(&Self::user_dir()?, "config", warnings)
+                .await?
+                .into_iter()
+                .collect()),
+        }
+    }
+
+    /// Returns the nearest project config file, if any
+    pub(crate) async fn project_file(warnings: &mut Vec<Problem>) -> Result<Option<PathBuf>> {
+        find_project_config(&env::current_dir()?, warnings).await
+    }
+
+    /// Returns the directory of the user config file
+    fn user_dir() -> Result<PathBuf> {
+        Ok(get_config_home()?.join("get"))
+    }
+
+    /// Converts the given config file, or the user config file, to another
+    /// format, returning the new path
+    pub(crate) async fn migrate(path: Option<&str>, to: Format) -> Result<PathBuf> {
+        let path = match path {
+            Some(path) => PathBuf::from(path),
+            None => format::find(&Self::user_dir()?, "config", &mut vec![])
+                .await?
+                .context("No user config file to migrate")?,
+        };
+
+        format::migrate::<ConfigFile>(&path, to).await
+    }
+
+    /// Validates a config file, returning the problems found
+    pub(crate) async fn check_file(path: &Path) -> Vec<Problem> {
+        match read_file(path).await {
+            Ok((config_file, mut problems)) => {
+                problems.extend(config_file.problems(path));
+                problems
+            }
+            Err(err) => vec![Problem::Error(err.to_string())],
         }
     }
 
-    pub async fn load() -> Result<Self> {
-        let path = get_config_home()?.join("get").join("config.json");
+    /// Validates a project config file, including that it only sets the keys
+    /// allowed for its directory by the given user config file
+    pub(crate) async fn check_project_file(path: &Path, user_config: Option<&str>) -> Vec<Problem> {
+        let mut problems = Self::check_file(path).await;
+
+        if problems.iter().any(Problem::is_error) {
+            return problems;
+        }
+
+        let trust = async {
+            let mut layers = vec![];
+            for user_path in Self::user_files(user_config, &mut vec![]).await? {
+                layers.push(read_layer(user_path, &mut vec![]).await?);
+            }
+            layers.extend(env_layers(env::vars())?);
+
+            let (_, layer) = read_layer(path.to_path_buf(), &mut vec![]).await?;
+            Ok::<_, anyhow::Error>(check_trust(path, &layer, &trusted_projects(layers.iter())))
+        };
 
-        match File::open(path).await {
-            Ok(mut file) => Self::load_from_file(&mut file).await,
-            Err(err) if err.kind() == ErrorKind::NotFound => Ok(Self::default()),
-            Err(err) => Err(err).context("open config file"),
+        match trust.await {
+            Ok(message) => problems.extend(message.map(Problem::Error)),
+            Err(err) => problems.push(Problem::Error(err.to_string())),
         }
+
+        problems
     }
+}
+
+impl LayeredConfig {
+    /// Returns each effective value by its dotted path, along with its origin
+    pub fn entries(&self) -> Result<Vec<(String, Value, Origin)>> {
+        let mut entries = vec![];
+        flatten(
+            serde_json::to_value(&self.config)?,
+            String::new(),
+            &mut entries,
+        );
 
-    pub async fn load_from_path(path: &str) -> Result<Self> {
-        let mut file = File::open(path).await?;
-        Self::load_from_file(&mut file).await
+        Ok(entries
+            .into_iter()
+            .map(|(path, value)| {
+                let origin = self.origin(&path);
+                (path, value, origin)
+            })
+            .collect())
     }
 
-    async fn load_from_file(file: &mut File) -> Result<Self> {
-        let mut dest = Vec::new();
-        file.read_to_end(&mut dest).await?;
+    /// Returns the origin of the value at the path, or of the closest parent
+    /// set as a whole
+    fn origin(&self, path: &str) -> Origin {
+        let mut path = path;
 
-        let config_file: ConfigFile = serde_json::from_slice(&dest).context("parse config file")?;
+        loop {
+            if let Some(origin) = self.origins.get(path) {
+                return origin.clone();
+            }
 
-        Ok(Config {
+            match path.rfind('.') {
+                Some(index) => path = &path[..index],
+                None => return Origin::Default,
+            }
+        }
+    }
+}
+
+impl From<ConfigFile> for Config {
+    fn from(config_file: ConfigFile) -> Self {
+        let default = Config::default();
+
+        Config {
             fallback_hostname: config_file
                 .fallback_hostname
-                .unwrap_or(FALLBACK_HOSTNAME.to_string()),
+                .unwrap_or(default.fallback_hostname),
 
-            http_hostnames: config_file
-                .http_hostnames
-                .unwrap_or(vec![FALLBACK_HOSTNAME.to_string()]),
-        })
+            http_hostnames: config_file.http_hostnames.unwrap_or(default.http_hostnames),
+
+            https_hostnames: config_file
+                .https_hostnames
+                .unwrap_or(default.https_hostnames),
+
+            signing_profiles: config_file
+                .signing_profiles
+                .unwrap_or(default.signing_profiles),
+
+            transport: config_file.transport.unwrap_or(default.transport),
+
+            aliases: config_file.aliases.unwrap_or(default.aliases),
+
+            environments: config_file.environments.unwrap_or(default.environments),
+
+            trusted_projects: config_file
+                .trusted_projects
+                .unwrap_or(default.trusted_projects),
+        }
     }
 }
 
@@ -64,8 +369,411 @@ fn get_config_home() -> Result<PathBuf> {
     }
 }
 
-#[derive(Debug, Deserialize)]
+/// Reads and parses a config file, returning warnings for unknown keys
+async fn read_file(path: &Path) -> Result<(ConfigFile, Vec<Problem>)> {
+    let contents = fs::read(path)
+        .await
+        .with_context(|| format!("read config file {:?}", path))?;
+
+    Format::from_path(path).parse(&contents, path)
+}
+
+/// Reads a config file as a layer
+async fn read_layer(path: PathBuf, warnings: &mut Vec<Problem>) -> Result<(Origin, Value)> {
+    let (config_file, problems) = read_file(&path).await?;
+    warnings.extend(problems);
+    Ok((Origin::File(path), serde_json::to_value(config_file)?))
+}
+
+/// Returns the canonical trusted project directories, as set by the last layer
+/// to set them
+///
+/// Directories that don't exist are skipped.
+fn trusted_projects<'a>(layers: impl Iterator<Item = &'a (Origin, Value)>) -> Vec<PathBuf> {
+    let Some(Value::Array(dirs)) = layers
+        .filter_map(|(_, layer)| layer.get("trusted_projects"))
+        .last()
+    else {
+        return vec![];
+    };
+
+    dirs.iter()
+        .filter_map(Value::as_str)
+        .filter_map(|dir| expand_home(dir).ok()?.canonicalize().ok())
+        .collect()
+}
+
+/// Returns an error message if the project config file sets keys other than
+/// `PROJECT_KEYS` without its directory being trusted
+fn check_trust(path: &Path, layer: &Value, trusted: &[PathBuf]) -> Option<String> {
+    let untrusted: Vec<&str> = layer
+        .as_object()?
+        .keys()
+        .map(String::as_str)
+        .filter(|key| !PROJECT_KEYS.contains(key))
+        .collect();
+
+    if untrusted.is_empty() {
+        return None;
+    }
+
+    // The project directory contains either `.get.json` or `.get/`.
+    let dir = path.parent()?;
+    let dir = match dir.file_name() {
+        Some(name) if name == ".get" => dir.parent()?,
+        _ => dir,
+    };
+
+    let dir = dir.canonicalize().unwrap_or(dir.to_path_buf());
+    if trusted.contains(&dir) {
+        return None;
+    }
+
+    Some(format!(
+        "{}: {} can only be set in a trusted project; add {:?} to trusted_projects in the user config",
+        path.display(),
+        untrusted.join(", "),
+        dir.display().to_string()
+    ))
+}
+
+/// Finds the nearest project config file in the directory or its parents
+async fn find_project_config(dir: &Path, warnings: &mut Vec<Problem>) -> Result<Option<PathBuf>> {
+    for dir in dir.ancestors() {
+        if let Some(path) = format::find(dir, ".get", warnings).await? {
+            return Ok(Some(path));
+        }
+
+        if let Some(path) = format::find(&dir.join(".get"), "config", warnings).await? {
+            return Ok(Some(path));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Creates a layer for each `GET_*` environment variable naming a config key
+///
+/// Values are parsed as JSON, falling back to a string and then to a
+/// comma-separated list, whichever is valid for the key.
+fn env_layers(vars: impl Iterator<Item = (String, String)>) -> Result<Vec<(Origin, Value)>> {
+    let mut vars: Vec<(String, String)> = vars
+        .filter(|(name, _)| {
+            name.strip_prefix(ENV_PREFIX)
+                .and_then(|name| name.split("__").next())
+                .is_some_and(|key| is_config_key(&key.to_lowercase()))
+        })
+        .collect();
+    vars.sort();
+
+    let mut layers = vec![];
+
+    for (name, raw) in vars {
+        let path: Vec<String> = name[ENV_PREFIX.len()..]
+            .split("__")
+            .map(str::to_lowercase)
+            .collect();
+
+        let candidates = [
+            serde_json::from_str(&raw).ok(),
+            Some(Value::String(raw.clone())),
+            Some(Value::Array(
+                raw.split(',')
+                    .map(|item| Value::String(item.trim().to_string()))
+                    .collect(),
+            )),
+        ];
+
+        let layer = candidates
+            .into_iter()
+            .flatten()
+            .map(|value| {
+                path.iter().rev().fold(value, |value, key| {
+                    Value::Object(Map::from_iter([(key.clone(), value)]))
+                })
+            })
+            .find(|layer| serde_json::from_value::<ConfigFile>(layer.clone()).is_ok())
+            .with_context(|| format!("invalid value for ${}", name))?;
+
+        layers.push((Origin::Env(name), layer));
+    }
+
+    Ok(layers)
+}
+
+/// Returns whether the key is a top-level config key
+fn is_config_key(key: &str) -> bool {
+    let mut known = true;
+    let layer = Value::Object(Map::from_iter([(key.to_string(), Value::Null)]));
+    let _: Result<ConfigFile, _> = serde_ignored::deserialize(layer, |_| known = false);
+    known
+}
+
+/// Signing profiles are replaced as a whole rather than merged field by field
+fn is_atomic(path: &str) -> bool {
+    path.starts_with("signing_profiles.")
+}
+
+fn join_path(parent: &str, key: &str) -> String {
+    if parent.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", parent, key)
+    }
+}
+
+/// Merges a layer into the config, recording the origin of each value set
+fn merge(
+    base: &mut Value,
+    layer: Value,
+    path: &str,
+    origin: &Origin,
+    origins: &mut BTreeMap<String, Origin>,
+) {
+    match (base, layer) {
+        (_, Value::Null) => {}
+
+        (base, Value::Object(layer))
+            if !is_atomic(path) && (base.is_object() || base.is_null()) =>
+        {
+            if base.is_null() {
+                *base = Value::Object(Map::new());
+            }
+
+            let Value::Object(base) = base else {
+                unreachable!("base is an object")
+            };
+
+            for (key, value) in layer {
+                let path = join_path(path, &key);
+                let base = base.entry(key).or_insert(Value::Null);
+                merge(base, value, &path, origin, origins);
+            }
+        }
+
+        (base, layer) => {
+            *base = layer;
+
+            let prefix = format!("{}.", path);
+            origins.retain(|key, _| !key.starts_with(&prefix));
+            origins.insert(path.to_string(), origin.clone());
+        }
+    }
+}
+
+/// Collects the values of a config by their dotted paths
+fn flatten(value: Value, path: String, entries: &mut Vec<(String, Value)>) {
+    match value {
+        Value::Object(map) if !is_atomic(&path) && !map.is_empty() => {
+            for (key, value) in map {
+                flatten(value, join_path(&path, &key), entries);
+            }
+        }
+        value => entries.push((path, value)),
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
 struct ConfigFile {
+    #[serde(skip_serializing_if = "Option::is_none")]
     fallback_hostname: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     http_hostnames: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    https_hostnames: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signing_profiles: Option<HashMap<String, SigningProfile>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    transport: Option<Transport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    aliases: Option<BTreeMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    environments: Option<BTreeMap<String, BTreeMap<String, String>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trusted_projects: Option<Vec<String>>,
+}
+
+impl ConfigFile {
+    /// Checks values that are valid JSON but can't be used
+    fn problems(&self, path: &Path) -> Vec<Problem> {
+        let mut problems = vec![];
+
+        for (name, profile) in self.signing_profiles.iter().flatten() {
+            let location = format!("{}: signing_profiles.{}", path.display(), name);
+
+            let headers = [
+                profile.timestamp_header.as_ref(),
+                profile.body_digest_header.as_ref(),
+                Some(&profile.signature_header),
+            ];
+
+            let part_headers = profile.parts.iter().filter_map(|part| match part {
+                SigningPart::Header(name) => Some(name),
+                _ => None,
+            });
+
+            for header in headers.into_iter().flatten().chain(part_headers) {
+                problems.extend(validate::check_header_name(header, &location));
+            }
+        }
+
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn layered(layers: Vec<(Origin, Value)>) -> LayeredConfig {
+        let mut merged = Value::Object(Map::new());
+        let mut origins = BTreeMap::new();
+
+        for (origin, layer) in layers {
+            merge(&mut merged, layer, "", &origin, &mut origins);
+        }
+
+        LayeredConfig {
+            config: serde_json::from_value::<ConfigFile>(merged).unwrap().into(),
+            origins,
+            warnings: vec![],
+        }
+    }
+
+    #[test]
+    fn merge_layers_by_key() {
+        let user = Origin::File(PathBuf::from("user.json"));
+        let project = Origin::File(PathBuf::from(".get.json"));
+
+        let config = layered(vec![
+            (
+                user.clone(),
+                json!({
+                    "aliases": {"gh": "https://api.github.com", "api": "http://localhost"},
+                    "transport": {"timeout": 5.0},
+                }),
+            ),
+            (
+                project.clone(),
+                json!({
+                    "aliases": {"api": "https://api.example.com"},
+                    "transport": {"proxy": "http://proxy:8080"},
+                }),
+            ),
+        ]);
+
+        assert_eq!(config.config.aliases["gh"], "https://api.github.com");
+        assert_eq!(config.config.aliases["api"], "https://api.example.com");
+        assert_eq!(config.config.transport.timeout, Some(5.0));
+
+        assert_eq!(config.origin("aliases.gh"), user);
+        assert_eq!(config.origin("aliases.api"), project);
+        assert_eq!(config.origin("transport.timeout"), user);
+        assert_eq!(config.origin("transport.proxy"), project);
+        assert_eq!(config.origin("fallback_hostname"), Origin::Default);
+    }
+
+    #[test]
+    fn override_with_env_vars() {
+        let vars = [
+            ("GET_FALLBACK_HOSTNAME", "example.com"),
+            ("GET_HTTP_HOSTNAMES", "a.test, b.test"),
+            ("GET_TRANSPORT__HTTP_VERSION", "2"),
+            ("GET_TRANSPORT__TIMEOUT", "2.5"),
+            ("GET_ALIASES__GH", "https://api.github.com"),
+            ("GET_UNRELATED", "ignored"),
+            ("PATH", "/usr/bin"),
+        ]
+        .map(|(name, value)| (name.to_string(), value.to_string()));
+
+        let layers = env_layers(vars.into_iter()).unwrap();
+        assert_eq!(layers.len(), 5);
+
+        let config = layered(layers);
+
+        assert_eq!(config.config.fallback_hostname, "example.com");
+        assert_eq!(config.config.http_hostnames, vec!["a.test", "b.test"]);
+        assert_eq!(config.config.transport.timeout, Some(2.5));
+        assert_eq!(config.config.aliases["gh"], "https://api.github.com");
+        assert_eq!(
+            config.origin("transport.http_version"),
+            Origin::Env("GET_TRANSPORT__HTTP_VERSION".to_string())
+        );
+        assert_eq!(config.origin("transport.proxy"), Origin::Default);
+    }
+
+    #[test]
+    fn reject_invalid_env_var() {
+        let vars = [("GET_TRANSPORT__TIMEOUT".to_string(), "soon".to_string())];
+        assert!(env_layers(vars.into_iter()).is_err());
+    }
+
+    #[tokio::test]
+    async fn find_nearest_project_config() {
+        let root = env::temp_dir().join(format!("get-config-test-{}", std::process::id()));
+        let nested = root.join("a").join("b");
+        fs::create_dir_all(root.join("a").join(".get"))
+            .await
+            .unwrap();
+        fs::create_dir_all(&nested).await.unwrap();
+        fs::write(root.join(".get.json"), "{}").await.unwrap();
+        fs::write(root.join("a").join(".get").join("config.json"), "{}")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            find_project_config(&nested, &mut vec![]).await.unwrap(),
+            Some(root.join("a").join(".get").join("config.json"))
+        );
+        assert_eq!(
+            find_project_config(&root, &mut vec![]).await.unwrap(),
+            Some(root.join(".get.json"))
+        );
+
+        fs::remove_dir_all(&root).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn reject_untrusted_project_keys() {
+        let root = env::temp_dir().join(format!("get-trust-test-{}", std::process::id()));
+        fs::create_dir_all(root.join(".get")).await.unwrap();
+        let path = root.join(".get").join("config.json");
+
+        let layer = json!({
+            "environments": {"dev": {"host": "localhost"}},
+            "transport": {"proxy": "http://attacker:8080"},
+        });
+
+        let message = check_trust(&path, &layer, &[]).unwrap();
+        assert!(message.contains("transport can only be set in a trusted project"));
+        assert!(check_trust(&path, &json!({"environments": {}}), &[]).is_none());
+
+        // A trusted directory may set anything.
+        fs::write(&path, "{}").await.unwrap();
+        let trust = (
+            Origin::File(PathBuf::from("user.json")),
+            json!({"trusted_projects": [root.to_string_lossy()]}),
+        );
+        let trusted = trusted_projects([trust].iter());
+        assert!(check_trust(&path, &layer, &trusted).is_none());
+
+        fs::remove_dir_all(&root).await.unwrap();
+    }
+
+    #[test]
+    fn reject_project_scheme_downgrade() {
+        let path = Path::new("/project/.get.json");
+
+        let layer = json!({"http_hostnames": ["api.example.com"]});
+        let message = check_trust(path, &layer, &[]).unwrap();
+        assert!(message.contains("http_hostnames can only be set in a trusted project"));
+
+        let layer = json!({"aliases": {"api": "http://api.example.com"}});
+        let message = check_trust(path, &layer, &[]).unwrap();
+        assert!(message.contains("aliases can only be set in a trusted project"));
+
+        assert!(check_trust(path, &json!({"https_hostnames": ["a.com"]}), &[]).is_none());
+    }
 }
@@ -0,0 +1,360 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+};
+
+use anyhow::{bail, Context, Result};
+use http::{header::WWW_AUTHENTICATE, HeaderMap, HeaderValue};
+use md5::Md5;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+/// The hash algorithms supported for digest authentication
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Algorithm {
+    Md5,
+    Md5Sess,
+    Sha256,
+    Sha256Sess,
+}
+
+impl Algorithm {
+    fn from_str(name: &str) -> Option<Self> {
+        match name.to_ascii_uppercase().as_str() {
+            "MD5" => Some(Self::Md5),
+            "MD5-SESS" => Some(Self::Md5Sess),
+            "SHA-256" => Some(Self::Sha256),
+            "SHA-256-SESS" => Some(Self::Sha256Sess),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        match self {
+            Self::Md5 => "MD5",
+            Self::Md5Sess => "MD5-sess",
+            Self::Sha256 => "SHA-256",
+            Self::Sha256Sess => "SHA-256-sess",
+        }
+    }
+
+    fn is_sess(&self) -> bool {
+        matches!(self, Self::Md5Sess | Self::Sha256Sess)
+    }
+
+    fn strength(&self) -> u8 {
+        match self {
+            Self::Md5 | Self::Md5Sess => 0,
+            Self::Sha256 | Self::Sha256Sess => 1,
+        }
+    }
+
+    fn hash(&self, data: &str) -> String {
+        match self {
+            Self::Md5 | Self::Md5Sess => hex::encode(Md5::digest(data)),
+            Self::Sha256 | Self::Sha256Sess => hex::encode(Sha256::digest(data)),
+        }
+    }
+}
+
+/// A digest authentication challenge sent by a server in a
+/// `WWW-Authenticate` header
+#[derive(Debug, PartialEq)]
+pub struct Challenge {
+    pub realm: String,
+    pub nonce: String,
+    pub opaque: Option<String>,
+    pub algorithm: Algorithm,
+    pub qop_auth: bool,
+    pub stale: bool,
+}
+
+impl Challenge {
+    /// Finds the strongest supported digest challenge in the given headers
+    pub fn from_headers(headers: &HeaderMap) -> Result<Option<Self>> {
+        let mut best: Option<Self> = None;
+
+        for value in headers.get_all(WWW_AUTHENTICATE) {
+            let Some(challenge) = Self::parse(value.to_str()?)? else {
+                continue;
+            };
+
+            match &best {
+                Some(b) if b.algorithm.strength() >= challenge.algorithm.strength() => {}
+                _ => best = Some(challenge),
+            }
+        }
+
+        Ok(best)
+    }
+
+    /// Parses a single `WWW-Authenticate` header value
+    ///
+    /// Returns `None` if the value is not a digest challenge or uses an
+    /// unsupported algorithm.
+    pub fn parse(value: &str) -> Result<Option<Self>> {
+        let value = value.trim_start();
+
+        let Some((scheme, params)) = value.split_once(' ') else {
+            return Ok(None);
+        };
+
+        if !scheme.eq_ignore_ascii_case("digest") {
+            return Ok(None);
+        }
+
+        let params = parse_params(params)?;
+
+        let algorithm = match params.get("algorithm") {
+            Some(name) => match Algorithm::from_str(name) {
+                Some(algorithm) => algorithm,
+                None => return Ok(None),
+            },
+            None => Algorithm::Md5,
+        };
+
+        let qop_auth = match params.get("qop") {
+            Some(qop) => {
+                if !qop.split(',').any(|q| q.trim() == "auth") {
+                    bail!("Unsupported digest qop \"{}\"", qop);
+                }
+
+                true
+            }
+            None => false,
+        };
+
+        Ok(Some(Self {
+            realm: params.get("realm").cloned().unwrap_or_default(),
+            nonce: params.get("nonce").cloned().context("digest nonce")?,
+            opaque: params.get("opaque").cloned(),
+            algorithm,
+            qop_auth,
+            stale: params
+                .get("stale")
+                .is_some_and(|stale| stale.eq_ignore_ascii_case("true")),
+        }))
+    }
+
+    /// Builds the Authorization header value answering this challenge
+    pub fn authorize(
+        &self,
+        username: &str,
+        password: &str,
+        method: &str,
+        uri: &str,
+    ) -> Result<HeaderValue> {
+        let nc = next_nonce_count(&self.nonce);
+        let cnonce = generate_cnonce();
+        let value = self.authorization(username, password, method, uri, nc, &cnonce);
+
+        let mut value = HeaderValue::from_str(&value).context("valid authorization header")?;
+        value.set_sensitive(true);
+
+        Ok(value)
+    }
+
+    fn authorization(
+        &self,
+        username: &str,
+        password: &str,
+        method: &str,
+        uri: &str,
+        nc: u32,
+        cnonce: &str,
+    ) -> String {
+        let algorithm = self.algorithm;
+        let nc = format!("{:08x}", nc);
+
+        let mut ha1 = algorithm.hash(&format!("{}:{}:{}", username, self.realm, password));
+        if algorithm.is_sess() {
+            ha1 = algorithm.hash(&format!("{}:{}:{}", ha1, self.nonce, cnonce));
+        }
+
+        let ha2 = algorithm.hash(&format!("{}:{}", method, uri));
+
+        let response = if self.qop_auth {
+            algorithm.hash(&format!(
+                "{}:{}:{}:{}:auth:{}",
+                ha1, self.nonce, nc, cnonce, ha2
+            ))
+        } else {
+            algorithm.hash(&format!("{}:{}:{}", ha1, self.nonce, ha2))
+        };
+
+        let mut value = format!(
+            r#"Digest username="{}", realm="{}", nonce="{}", uri="{}", algorithm={}, response="{}""#,
+            quote(username),
+            quote(&self.realm),
+            quote(&self.nonce),
+            quote(uri),
+            algorithm.as_str(),
+            response
+        );
+
+        if self.qop_auth {
+            value.push_str(&format!(r#", qop=auth, nc={}, cnonce="{}""#, nc, cnonce));
+        }
+
+        if let Some(opaque) = &self.opaque {
+            value.push_str(&format!(r#", opaque="{}""#, quote(opaque)));
+        }
+
+        value
+    }
+}
+
+/// Returns the next nonce count for a server nonce
+///
+/// Counts are kept for the lifetime of the process, so that a nonce reused
+/// across several requests is never sent with the same count twice.
+fn next_nonce_count(nonce: &str) -> u32 {
+    static COUNTS: OnceLock<Mutex<HashMap<String, u32>>> = OnceLock::new();
+
+    let mut counts = COUNTS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|err| err.into_inner());
+
+    let count = counts.entry(nonce.to_string()).or_insert(0);
+    *count += 1;
+    *count
+}
+
+fn generate_cnonce() -> String {
+    let mut bytes = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+fn quote(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Parses comma-separated `key=value` auth-params, where values may be quoted
+fn parse_params(input: &str) -> Result<HashMap<String, String>> {
+    let mut params = HashMap::new();
+    let mut chars = input.chars().peekable();
+
+    loop {
+        while chars.next_if(|c| c.is_whitespace() || *c == ',').is_some() {}
+
+        let mut key = String::new();
+        while let Some(c) = chars.next_if(|c| *c != '=') {
+            key.push(c);
+        }
+
+        if key.is_empty() {
+            break;
+        }
+
+        if chars.next() != Some('=') {
+            bail!("Invalid digest challenge parameter \"{}\"", key.trim());
+        }
+
+        let mut value = String::new();
+        if chars.next_if_eq(&'"').is_some() {
+            loop {
+                match chars.next() {
+                    Some('\\') => value.extend(chars.next()),
+                    Some('"') => break,
+                    Some(c) => value.push(c),
+                    None => bail!("Unterminated quoted string in digest challenge"),
+                }
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| *c != ',') {
+                value.push(c);
+            }
+        }
+
+        params.insert(key.trim().to_ascii_lowercase(), value.trim().to_string());
+    }
+
+    Ok(params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The examples from RFC 7616, section 3.9.1
+    const CHALLENGE: &str = r#"Digest realm="http-auth@example.org", qop="auth, auth-int", algorithm=SHA-256, nonce="7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v", opaque="FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS""#;
+    const CNONCE: &str = "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ";
+
+    #[test]
+    fn parse_challenge() {
+        let challenge = Challenge::parse(CHALLENGE).unwrap().unwrap();
+
+        assert_eq!(
+            challenge,
+            Challenge {
+                realm: "http-auth@example.org".to_string(),
+                nonce: "7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v".to_string(),
+                opaque: Some("FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS".to_string()),
+                algorithm: Algorithm::Sha256,
+                qop_auth: true,
+                stale: false,
+            }
+        );
+    }
+
+    #[test]
+    fn ignore_other_schemes() {
+        assert_eq!(Challenge::parse(r#"Basic realm="foo""#).unwrap(), None);
+    }
+
+    #[test]
+    fn prefer_strongest_challenge() {
+        let mut headers = HeaderMap::new();
+        headers.append(
+            WWW_AUTHENTICATE,
+            HeaderValue::from_str(&CHALLENGE.replace("SHA-256", "MD5")).unwrap(),
+        );
+        headers.append(WWW_AUTHENTICATE, HeaderValue::from_static(CHALLENGE));
+
+        let challenge = Challenge::from_headers(&headers).unwrap().unwrap();
+        assert_eq!(challenge.algorithm, Algorithm::Sha256);
+    }
+
+    #[test]
+    fn sha256_response() {
+        let challenge = Challenge::parse(CHALLENGE).unwrap().unwrap();
+        let value = challenge.authorization(
+            "Mufasa",
+            "Circle of Life",
+            "GET",
+            "/dir/index.html",
+            1,
+            CNONCE,
+        );
+
+        assert!(value.contains(
+            r#"response="753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1""#
+        ));
+        assert!(value.contains("nc=00000001"));
+    }
+
+    #[test]
+    fn md5_response() {
+        let challenge = Challenge::parse(&CHALLENGE.replace("SHA-256", "MD5"))
+            .unwrap()
+            .unwrap();
+        let value = challenge.authorization(
+            "Mufasa",
+            "Circle of Life",
+            "GET",
+            "/dir/index.html",
+            1,
+            CNONCE,
+        );
+
+        assert!(value.contains(r#"response="8ca523f5e9506fed4657c9700eebdbec""#));
+    }
+
+    #[test]
+    fn count_nonce_uses() {
+        assert_eq!(next_nonce_count("count_nonce_uses"), 1);
+        assert_eq!(next_nonce_count("count_nonce_uses"), 2);
+    }
+}
@@ -0,0 +1,233 @@
+use std::env;
+
+use anyhow::{anyhow, bail, Context, Result};
+use argon2::Argon2;
+use chacha20poly1305::{
+    aead::{Aead, Payload},
+    ChaCha20Poly1305, KeyInit,
+};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use tokio::fs;
+
+use crate::secret::expand_home;
+
+/// Identifies an encrypted file, followed by a format version
+const MAGIC: &[u8] = b"GETENC";
+const VERSION: u8 = 1;
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+/// The length of the header, which is authenticated along with the contents:
+/// the magic bytes, version, key kind, salt and nonce
+const HEADER_LEN: usize = MAGIC.len() + 2 + SALT_LEN + NONCE_LEN;
+
+/// The environment variable naming a key file, which takes precedence over a
+/// passphrase
+const KEY_FILE_VAR: &str = "GET_SESSION_KEY_FILE";
+
+/// The environment variable holding the passphrase, which is prompted for
+/// when unset
+const PASSPHRASE_VAR: &str = "GET_SESSION_PASSPHRASE";
+
+/// The secret an encrypted file is encrypted with
+pub enum Key {
+    /// The contents of a key file, e.g. 32 random bytes, which are hashed
+    /// with SHA-256
+    File(Vec<u8>),
+
+    /// A passphrase, which is stretched with Argon2id
+    Passphrase(String),
+}
+
+impl Key {
+    fn kind(&self) -> u8 {
+        match self {
+            Self::File(_) => 0,
+            Self::Passphrase(_) => 1,
+        }
+    }
+
+    /// Gets the key to encrypt a file with, from a key file, the passphrase
+    /// variable or a prompt
+    pub async fn for_encryption() -> Result<Self> {
+        if let Some(key) = Self::from_key_file().await? {
+            return Ok(key);
+        }
+
+        if let Ok(passphrase) = env::var(PASSPHRASE_VAR) {
+            return Ok(Self::Passphrase(passphrase));
+        }
+
+        let passphrase = rpassword::prompt_password("New session store passphrase: ")
+            .context("read passphrase")?;
+        let confirmation =
+            rpassword::prompt_password("Confirm passphrase: ").context("read passphrase")?;
+
+        if passphrase != confirmation {
+            bail!("Passphrases do not match");
+        }
+
+        if passphrase.is_empty() {
+            bail!("Passphrase must not be empty");
+        }
+
+        Ok(Self::Passphrase(passphrase))
+    }
+
+    /// Gets the key an encrypted file was encrypted with, of the kind
+    /// recorded in its header
+    pub async fn for_decryption(contents: &[u8]) -> Result<Self> {
+        match contents.get(MAGIC.len() + 1) {
+            Some(0) => Self::from_key_file()
+                .await?
+                .with_context(|| format!("Encrypted with a key file; set ${}", KEY_FILE_VAR)),
+
+            Some(1) => match env::var(PASSPHRASE_VAR) {
+                Ok(passphrase) => Ok(Self::Passphrase(passphrase)),
+                Err(_) => rpassword::prompt_password("Session store passphrase: ")
+                    .map(Self::Passphrase)
+                    .context("read passphrase"),
+            },
+
+            _ => bail!("Unsupported encryption header"),
+        }
+    }
+
+    async fn from_key_file() -> Result<Option<Self>> {
+        let Ok(path) = env::var(KEY_FILE_VAR) else {
+            return Ok(None);
+        };
+
+        let contents = fs::read(expand_home(&path)?)
+            .await
+            .with_context(|| format!("read key file {}", path))?;
+
+        if contents.is_empty() {
+            bail!("Key file {} is empty", path);
+        }
+
+        Ok(Some(Self::File(contents)))
+    }
+
+    fn cipher(&self, salt: &[u8]) -> Result<ChaCha20Poly1305> {
+        let mut key = [0; 32];
+
+        match self {
+            Self::File(contents) => key.copy_from_slice(&Sha256::digest(contents)),
+            Self::Passphrase(passphrase) => Argon2::default()
+                .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+                .map_err(|err| anyhow!("derive key: {}", err))?,
+        }
+
+        Ok(ChaCha20Poly1305::new(&key.into()))
+    }
+}
+
+/// Returns whether the contents were written by [`encrypt`]
+pub fn is_encrypted(contents: &[u8]) -> bool {
+    contents.starts_with(MAGIC)
+}
+
+/// Encrypts the contents with ChaCha20-Poly1305, under a random salt and
+/// nonce stored in the header
+pub fn encrypt(plaintext: &[u8], key: &Key) -> Result<Vec<u8>> {
+    let mut salt = [0; SALT_LEN];
+    let mut nonce = [0; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut salt);
+    rand::thread_rng().fill_bytes(&mut nonce);
+
+    let mut contents = Vec::with_capacity(HEADER_LEN + plaintext.len() + 16);
+    contents.extend_from_slice(MAGIC);
+    contents.extend_from_slice(&[VERSION, key.kind()]);
+    contents.extend_from_slice(&salt);
+    contents.extend_from_slice(&nonce);
+
+    let ciphertext = key
+        .cipher(&salt)?
+        .encrypt(
+            &nonce.into(),
+            Payload {
+                msg: plaintext,
+                aad: &contents,
+            },
+        )
+        .map_err(|_| anyhow!("Failed to encrypt"))?;
+
+    contents.extend_from_slice(&ciphertext);
+    Ok(contents)
+}
+
+/// Decrypts contents written by [`encrypt`]
+pub fn decrypt(contents: &[u8], key: &Key) -> Result<Vec<u8>> {
+    if !is_encrypted(contents) || contents.len() < HEADER_LEN {
+        bail!("Not an encrypted file");
+    }
+
+    let (header, ciphertext) = contents.split_at(HEADER_LEN);
+    let (version, kind) = (header[MAGIC.len()], header[MAGIC.len() + 1]);
+
+    if version != VERSION {
+        bail!("Unsupported encryption version {}", version);
+    }
+
+    if kind != key.kind() {
+        bail!("Encrypted with a different kind of key");
+    }
+
+    let salt = &header[MAGIC.len() + 2..][..SALT_LEN];
+    let nonce: [u8; NONCE_LEN] = header[HEADER_LEN - NONCE_LEN..].try_into()?;
+
+    key.cipher(salt)?
+        .decrypt(
+            &nonce.into(),
+            Payload {
+                msg: ciphertext,
+                aad: header,
+            },
+        )
+        .map_err(|_| anyhow!("Failed to decrypt: wrong key or corrupted file"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let plaintext = br#"{"example.com": {}}"#;
+
+        for key in [
+            Key::File(b"0123456789abcdef".to_vec()),
+            Key::Passphrase("hunter2".to_string()),
+        ] {
+            let contents = encrypt(plaintext, &key).unwrap();
+
+            assert!(is_encrypted(&contents));
+            assert!(!contents.windows(plaintext.len()).any(|w| w == plaintext));
+            assert_eq!(decrypt(&contents, &key).unwrap(), plaintext);
+        }
+    }
+
+    #[test]
+    fn reject_wrong_key() {
+        let contents = encrypt(b"{}", &Key::Passphrase("right".to_string())).unwrap();
+
+        assert!(decrypt(&contents, &Key::Passphrase("wrong".to_string())).is_err());
+        assert!(decrypt(&contents, &Key::File(b"right".to_vec())).is_err());
+    }
+
+    #[test]
+    fn reject_tampering() {
+        let key = Key::File(b"key".to_vec());
+        let contents = encrypt(b"{}", &key).unwrap();
+
+        // Both the header and the ciphertext are authenticated.
+        for index in [MAGIC.len() + 2, contents.len() - 1] {
+            let mut tampered = contents.clone();
+            tampered[index] ^= 1;
+            assert!(decrypt(&tampered, &key).is_err());
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use std::error::Error as _;
+
+use http::StatusCode;
+
+/// An error, categorised by what went wrong so that it maps to a stable exit
+/// code
+///
+/// | Code | Error                                                         |
+/// |------|---------------------------------------------------------------|
+/// | 1    | Anything else                                                 |
+/// | 2    | Invalid arguments or request components                       |
+/// | 3    | Invalid config, or an OAuth2 token couldn't be fetched        |
+/// | 4    | The session store was invalid, or couldn't be read or written |
+/// | 5    | Connecting failed, e.g. a DNS lookup or refusal               |
+/// | 6    | TLS failed, e.g. an untrusted certificate                     |
+/// | 7    | The request timed out                                         |
+/// | 8    | Too many redirects                                            |
+/// | 13   | A 3xx status, with `--check-status`                           |
+/// | 14   | A 4xx status, with `--check-status`                           |
+/// | 15   | A 5xx status, with `--check-status`                           |
+///
+/// Clap exits with 2 for arguments it rejects itself.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// Invalid arguments or request components
+    #[error(transparent)]
+    Usage(anyhow::Error),
+
+    /// An invalid config file, one that couldn't be read, or configured
+    /// OAuth2 credentials that a token couldn't be fetched with
+    #[error(transparent)]
+    Config(anyhow::Error),
+
+    /// The session store was invalid, or couldn't be read or written
+    #[error(transparent)]
+    Session(anyhow::Error),
+
+    /// Connecting to the server failed
+    #[error("Could not connect to {url}")]
+    Connect {
+        /// The URL of the request
+        url: String,
+        /// The error from sending the request
+        #[source]
+        source: reqwest::Error,
+    },
+
+    /// The TLS handshake failed
+    #[error("TLS error connecting to {url}")]
+    Tls {
+        /// The URL of the request
+        url: String,
+        /// The error from sending the request
+        #[source]
+        source: reqwest::Error,
+    },
+
+    /// The request timed out
+    #[error("Request to {url} timed out")]
+    Timeout {
+        /// The URL of the request
+        url: String,
+        /// The error from sending the request
+        #[source]
+        source: reqwest::Error,
+    },
+
+    /// The response redirected more times than allowed
+    #[error("Too many redirects from {url}")]
+    RedirectLimit {
+        /// The URL of the request
+        url: String,
+        /// The error from sending the request
+        #[source]
+        source: reqwest::Error,
+    },
+
+    /// The response had an unsuccessful status, when checking for one
+    #[error("HTTP status {0}")]
+    Status(StatusCode),
+
+    /// Anything else
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl Error {
+    /// Categorises an error from sending a request
+    pub(crate) fn from_reqwest(url: &str, source: reqwest::Error) -> Self {
+        let url = url.to_string();
+
+        if source.is_timeout() {
+            Self::Timeout { url, source }
+        } else if source.is_redirect() {
+            Self::RedirectLimit { url, source }
+        } else if source.is_connect() && is_tls(&source) {
+            Self::Tls { url, source }
+        } else if source.is_connect() {
+            Self::Connect { url, source }
+        } else {
+            Self::Other(source.into())
+        }
+    }
+
+    /// Returns the exit code for the error, as listed above
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::Other(_) => 1,
+            Self::Usage(_) => 2,
+            Self::Config(_) => 3,
+            Self::Session(_) => 4,
+            Self::Connect { .. } => 5,
+            Self::Tls { .. } => 6,
+            Self::Timeout { .. } => 7,
+            Self::RedirectLimit { .. } => 8,
+            Self::Status(status) => 10 + (status.as_u16() / 100) as u8,
+        }
+    }
+}
+
+/// Returns whether a connection error came from the TLS backend
+fn is_tls(err: &reqwest::Error) -> bool {
+    let mut source = err.source();
+
+    while let Some(cause) = source {
+        if cause.is::<native_tls::Error>() {
+            return true;
+        }
+        source = cause.source();
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use crate::transport::Transport;
+
+    use super::*;
+
+    /// Serves every connection with the response, or holds it open without
+    /// responding if there's none
+    async fn serve(response: Option<&'static str>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let mut streams = vec![];
+
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = vec![0; 4096];
+                let _ = stream.read(&mut request).await;
+
+                match response {
+                    Some(response) => stream.write_all(response.as_bytes()).await.unwrap(),
+                    None => streams.push(stream),
+                }
+            }
+        });
+
+        addr.to_string()
+    }
+
+    async fn send(url: &str, transport: Transport) -> Error {
+        let client = transport.client_builder().await.unwrap().build().unwrap();
+        let source = client.get(url).send().await.unwrap_err();
+
+        Error::from_reqwest(url, source)
+    }
+
+    #[test]
+    fn map_statuses_to_exit_codes() {
+        assert_eq!(Error::Status(StatusCode::FOUND).exit_code(), 13);
+        assert_eq!(Error::Status(StatusCode::NOT_FOUND).exit_code(), 14);
+        assert_eq!(Error::Status(StatusCode::BAD_GATEWAY).exit_code(), 15);
+    }
+
+    #[tokio::test]
+    async fn map_refused_connection_to_exit_code() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        drop(listener);
+
+        assert_eq!(send(&url, Transport::default()).await.exit_code(), 5);
+    }
+
+    #[tokio::test]
+    async fn map_tls_failure_to_exit_code() {
+        let addr = serve(Some("HTTP/1.1 400 Bad Request\r\n\r\n")).await;
+        let url = format!("https://{}/", addr);
+
+        assert_eq!(send(&url, Transport::default()).await.exit_code(), 6);
+    }
+
+    #[tokio::test]
+    async fn map_timeout_to_exit_code() {
+        let url = format!("http://{}/", serve(None).await);
+        let transport = Transport {
+            timeout: Some(0.2),
+            ..Transport::default()
+        };
+
+        assert_eq!(send(&url, transport).await.exit_code(), 7);
+    }
+
+    #[tokio::test]
+    async fn map_redirect_limit_to_exit_code() {
+        let addr = serve(Some(
+            "HTTP/1.1 302 Found\r\nlocation: /\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+        ))
+        .await;
+        let url = format!("http://{}/", addr);
+        let transport = Transport {
+            max_redirects: Some(2),
+            ..Transport::default()
+        };
+
+        assert_eq!(send(&url, transport).await.exit_code(), 8);
+    }
+
+    #[test]
+    fn keep_causes_of_wrapped_errors() {
+        let err = Error::Config(anyhow::anyhow!("unknown field").context("parse config.json"));
+
+        assert_eq!(err.to_string(), "parse config.json");
+        assert_eq!(err.source().unwrap().to_string(), "unknown field");
+        assert_eq!(err.exit_code(), 3);
+    }
+}
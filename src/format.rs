@@ -0,0 +1,306 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::ValueEnum;
+use serde::{de::DeserializeOwned, Deserializer, Serialize};
+use tokio::fs;
+
+use crate::{session::write_private, validate::Problem};
+
+/// A format for config and session files, detected by file extension
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Format {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl Format {
+    /// The formats in order of precedence, for when files exist in several
+    const ALL: [Self; 3] = [Self::Json, Self::Toml, Self::Yaml];
+
+    /// Returns the format of a file from its extension, defaulting to JSON
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Self::Toml,
+            Some("yaml" | "yml") => Self::Yaml,
+            _ => Self::Json,
+        }
+    }
+
+    pub fn extensions(&self) -> &'static [&'static str] {
+        match self {
+            Self::Json => &["json"],
+            Self::Toml => &["toml"],
+            Self::Yaml => &["yaml", "yml"],
+        }
+    }
+
+    /// Parses a file, returning a warning for each unknown key
+    ///
+    /// Syntax and type errors are reported with the line and column at which
+    /// they occurred.
+    pub fn parse<T: DeserializeOwned>(
+        &self,
+        contents: &[u8],
+        path: &Path,
+    ) -> Result<(T, Vec<Problem>)> {
+        let mut unknown = vec![];
+
+        let on_unknown = |key: serde_ignored::Path| {
+            // Options and newtypes are shown as "?" segments, which we omit.
+            let key = key.to_string();
+            let key: Vec<&str> = key.split('.').filter(|segment| *segment != "?").collect();
+
+            unknown.push(Problem::Warning(format!(
+                "{}: unknown key {}",
+                path.display(),
+                key.join(".")
+            )))
+        };
+
+        let value = match self {
+            Self::Json => {
+                let mut deserializer = serde_json::Deserializer::from_slice(contents);
+                let value = deserialize(&mut deserializer, on_unknown)
+                    .map_err(|err| located(path, err.line(), err.column(), &err.to_string()))?;
+                deserializer
+                    .end()
+                    .map_err(|err| located(path, err.line(), err.column(), &err.to_string()))?;
+                value
+            }
+
+            Self::Toml => {
+                let contents = std::str::from_utf8(contents)?;
+                deserialize(toml::Deserializer::new(contents), on_unknown).map_err(|err| {
+                    let (line, column) = match err.span() {
+                        Some(span) => line_column(contents, span.start),
+                        None => (0, 0),
+                    };
+                    located(path, line, column, err.message())
+                })?
+            }
+
+            Self::Yaml => deserialize(serde_yaml::Deserializer::from_slice(contents), on_unknown)
+                .map_err(|err| {
+                let (line, column) = err
+                    .location()
+                    .map_or((0, 0), |location| (location.line(), location.column()));
+                located(path, line, column, &err.to_string())
+            })?,
+        };
+
+        Ok((value, unknown))
+    }
+
+    /// Serializes a value in this format
+    pub fn serialize<T: Serialize>(&self, value: &T) -> Result<String> {
+        Ok(match self {
+            Self::Json => serde_json::to_string_pretty(value)? + "\n",
+            Self::Toml => toml::to_string_pretty(value)?,
+            Self::Yaml => serde_yaml::to_string(value)?,
+        })
+    }
+}
+
+/// Finds the file in the directory with the given name and any supported
+/// extension
+///
+/// If files exist in several formats, the first in order of precedence (JSON,
+/// then TOML, then YAML) is used, with a warning that the others are ignored.
+pub async fn find(dir: &Path, name: &str, warnings: &mut Vec<Problem>) -> Result<Option<PathBuf>> {
+    let mut found = vec![];
+
+    for format in Format::ALL {
+        for extension in format.extensions() {
+            let path = dir.join(format!("{}.{}", name, extension));
+            if fs::try_exists(&path).await? {
+                found.push(path);
+            }
+        }
+    }
+
+    let mut found = found.into_iter();
+    let path = found.next();
+
+    for ignored in found {
+        warnings.push(Problem::Warning(format!(
+            "{}: ignored in favour of {}",
+            ignored.display(),
+            path.as_ref().expect("a file was found").display()
+        )));
+    }
+
+    Ok(path)
+}
+
+/// Converts a file to another format, returning the path of the new file
+///
+/// The new file has the same name with the format's extension, and the
+/// original is kept with a `.bak` extension so that it no longer takes
+/// precedence. The new file is only readable by the user, as config and
+/// session files may hold secrets. Comments are not preserved.
+pub async fn migrate<T>(path: &Path, to: Format) -> Result<PathBuf>
+where
+    T: DeserializeOwned + Serialize,
+{
+    let from = Format::from_path(path);
+    if from == to {
+        bail!("{} is already in that format", path.display());
+    }
+
+    let contents = fs::read(path)
+        .await
+        .with_context(|| format!("read {}", path.display()))?;
+    let (value, _) = from.parse::<T>(&contents, path)?;
+
+    let new_path = path.with_extension(to.extensions()[0]);
+    if fs::try_exists(&new_path).await? {
+        bail!("{} already exists", new_path.display());
+    }
+
+    let backup = path.with_extension(format!("{}.bak", from.extensions()[0]));
+
+    write_private(&new_path, to.serialize(&value)?.as_bytes()).await?;
+    fs::rename(path, &backup).await?;
+
+    Ok(new_path)
+}
+
+fn deserialize<'de, D, T>(
+    deserializer: D,
+    on_unknown: impl FnMut(serde_ignored::Path),
+) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned,
+{
+    serde_ignored::deserialize(deserializer, on_unknown)
+}
+
+fn located(path: &Path, line: usize, column: usize, message: &str) -> anyhow::Error {
+    // The location is reported as a prefix instead.
+    let message = message
+        .rsplit_once(" at line ")
+        .map_or(message, |(message, _)| message);
+
+    anyhow!("{}:{}:{}: {}", path.display(), line, column, message)
+}
+
+/// Returns the one-based line and column of a byte offset
+fn line_column(contents: &str, offset: usize) -> (usize, usize) {
+    let before = &contents[..offset.min(contents.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.len() - before.rfind('\n').map_or(0, |index| index + 1) + 1;
+    (line, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    struct File {
+        hosts: Option<HashMap<String, Host>>,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    struct Host {
+        port: u16,
+    }
+
+    fn parse(format: Format, contents: &str) -> Result<(File, Vec<Problem>)> {
+        format.parse(contents.as_bytes(), Path::new("test"))
+    }
+
+    #[test]
+    fn warn_on_unknown_keys() {
+        let expected = vec![
+            Problem::Warning("test: unknown key hosts.a.prot".to_string()),
+            Problem::Warning("test: unknown key extra".to_string()),
+        ];
+
+        let (_, problems) = parse(
+            Format::Json,
+            r#"{"hosts": {"a": {"port": 80, "prot": 1}}, "extra": true}"#,
+        )
+        .unwrap();
+        assert_eq!(problems, expected);
+
+        let (_, problems) = parse(
+            Format::Toml,
+            "extra = true\n[hosts.a]\nport = 80\nprot = 1\n",
+        )
+        .unwrap();
+        assert_eq!(problems.len(), 2);
+
+        let (_, problems) = parse(
+            Format::Yaml,
+            "hosts:\n  a:\n    port: 80\n    prot: 1\nextra: true\n",
+        )
+        .unwrap();
+        assert_eq!(problems, expected);
+    }
+
+    #[test]
+    fn locate_errors() {
+        let err = parse(
+            Format::Json,
+            "{\n  \"hosts\": {\n    \"a\": {\"port\": \"80\"}\n  }\n}",
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "test:3:22: invalid type: string \"80\", expected u16"
+        );
+
+        let err = parse(Format::Toml, "# Hosts\n[hosts.a]\nport = \"80\"\n").unwrap_err();
+        assert!(err.to_string().starts_with("test:3:8: "));
+
+        let err = parse(Format::Yaml, "hosts:\n  a:\n    port: \"80\"\n").unwrap_err();
+        assert!(err.to_string().starts_with("test:3:11: "));
+    }
+
+    #[test]
+    fn convert_between_formats() {
+        let file = File {
+            hosts: Some(HashMap::from([("a".to_string(), Host { port: 80 })])),
+        };
+
+        for format in Format::ALL {
+            let contents = format.serialize(&file).unwrap();
+            let (parsed, _) = parse(format, &contents).unwrap();
+            assert_eq!(parsed, file);
+        }
+    }
+
+    #[tokio::test]
+    async fn migrate_to_private_file() {
+        let dir = std::env::temp_dir().join(format!("get-migrate-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).await.unwrap();
+        let path = dir.join("sessions.json");
+        fs::write(&path, r#"{"hosts": {"a": {"port": 80}}}"#)
+            .await
+            .unwrap();
+
+        let new_path = migrate::<File>(&path, Format::Toml).await.unwrap();
+        let contents = fs::read_to_string(&new_path).await.unwrap();
+        #[cfg(unix)]
+        let mode = {
+            use std::os::unix::fs::PermissionsExt;
+            fs::metadata(&new_path).await.unwrap().permissions().mode()
+        };
+        let backed_up = fs::try_exists(dir.join("sessions.json.bak")).await.unwrap();
+        fs::remove_dir_all(&dir).await.unwrap();
+
+        assert_eq!(new_path, dir.join("sessions.toml"));
+        assert_eq!(contents, "[hosts.a]\nport = 80\n");
+        assert!(backed_up);
+        #[cfg(unix)]
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
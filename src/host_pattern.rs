@@ -0,0 +1,153 @@
+use std::net::IpAddr;
+
+/// Returns whether the hostname matches any of the patterns
+///
+/// A pattern is one of:
+/// - A CIDR range, e.g. "10.0.0.0/8", matching IP addresses in the range
+/// - A glob, e.g. "*.local", where `*` matches any run of characters
+/// - A hostname or IP address, matched exactly
+///
+/// Hostnames are compared case-insensitively.
+pub fn matches_any(patterns: &[String], hostname: &str) -> bool {
+    patterns.iter().any(|pattern| matches(pattern, hostname))
+}
+
+pub fn matches(pattern: &str, hostname: &str) -> bool {
+    let hostname = hostname.to_ascii_lowercase();
+    let pattern = pattern.to_ascii_lowercase();
+
+    if let Some((network, prefix)) = pattern.split_once('/') {
+        return match (parse_ip(network), prefix.parse(), parse_ip(&hostname)) {
+            (Some(network), Ok(prefix), Some(ip)) => in_range(ip, network, prefix),
+            _ => false,
+        };
+    }
+
+    glob(pattern.as_bytes(), hostname.as_bytes())
+}
+
+/// Returns whether the hostname is a loopback or private network address,
+/// which is served over plain HTTP by default
+pub fn is_private_ip(hostname: &str) -> bool {
+    match parse_ip(hostname) {
+        Some(IpAddr::V4(ip)) => ip.is_loopback() || ip.is_private() || ip.is_link_local(),
+        Some(IpAddr::V6(ip)) => {
+            let segment = ip.segments()[0];
+            // Unique local (fc00::/7) and link-local (fe80::/10) addresses
+            ip.is_loopback() || segment & 0xfe00 == 0xfc00 || segment & 0xffc0 == 0xfe80
+        }
+        None => false,
+    }
+}
+
+/// Parses an IP address, which may be in brackets as in a URL
+fn parse_ip(input: &str) -> Option<IpAddr> {
+    input
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse()
+        .ok()
+}
+
+fn in_range(ip: IpAddr, network: IpAddr, prefix: u32) -> bool {
+    match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) if prefix <= 32 => {
+            let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(network)) if prefix <= 128 => {
+            let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
+
+/// Matches a glob with the iterative two-pointer algorithm
+///
+/// On a mismatch, only the most recent `*` is retried, consuming one more
+/// input character, so matching takes at worst O(pattern × input) time.
+fn glob(pattern: &[u8], input: &[u8]) -> bool {
+    let (mut p, mut i) = (0, 0);
+    // The position after the last star, and the input it was matched against
+    let mut star: Option<(usize, usize)> = None;
+
+    while i < input.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                p += 1;
+                star = Some((p, i));
+            }
+            Some(&c) if c == input[i] => {
+                p += 1;
+                i += 1;
+            }
+            _ => match star {
+                Some((star_p, star_i)) => {
+                    p = star_p;
+                    i = star_i + 1;
+                    star = Some((star_p, i));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn match_globs() {
+        assert!(matches("*.local", "printer.local"));
+        assert!(matches("*.local", "a.b.LOCAL"));
+        assert!(!matches("*.local", "local"));
+        assert!(!matches("*.local", "example.com"));
+        assert!(matches("dev-*.example.com", "dev-api.example.com"));
+        assert!(matches("localhost", "localhost"));
+        assert!(!matches("localhost", "localhost.example.com"));
+    }
+
+    #[test]
+    fn match_adjacent_and_trailing_stars() {
+        assert!(matches("*", ""));
+        assert!(matches("**.local", "a.local"));
+        assert!(matches("a*", "a"));
+        assert!(matches("*a*b", "xaxxb"));
+        assert!(!matches("*a*b", "xaxxbc"));
+    }
+
+    #[test]
+    fn match_pathological_glob_quickly() {
+        // Backtracking over every star would take exponential time here.
+        let hostname = "a".repeat(10_000);
+        assert!(!matches("*a*a*a*a*a*a*a*b", &hostname));
+    }
+
+    #[test]
+    fn match_cidr_ranges() {
+        assert!(matches("10.0.0.0/8", "10.1.2.3"));
+        assert!(!matches("10.0.0.0/8", "11.0.0.1"));
+        assert!(matches("192.168.1.0/24", "192.168.1.200"));
+        assert!(matches("0.0.0.0/0", "8.8.8.8"));
+        assert!(matches("fd00::/8", "[fd12::1]"));
+        assert!(!matches("10.0.0.0/8", "example.com"));
+        assert!(!matches("10.0.0.0/33", "10.0.0.1"));
+    }
+
+    #[test]
+    fn detect_private_ips() {
+        assert!(is_private_ip("127.0.0.1"));
+        assert!(is_private_ip("10.0.0.1"));
+        assert!(is_private_ip("172.16.5.4"));
+        assert!(is_private_ip("192.168.0.10"));
+        assert!(is_private_ip("[::1]"));
+        assert!(is_private_ip("fd00::1"));
+        assert!(!is_private_ip("8.8.8.8"));
+        assert!(!is_private_ip("172.32.0.1"));
+        assert!(!is_private_ip("example.com"));
+    }
+}
@@ -0,0 +1,235 @@
+use std::{
+    collections::BTreeMap,
+    io::ErrorKind,
+    net::IpAddr,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use http::header::STRICT_TRANSPORT_SECURITY;
+use reqwest::Response;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::session::get_data_home;
+
+/// A host's Strict-Transport-Security policy
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Policy {
+    /// The Unix timestamp at which the policy expires
+    pub expires_at: u64,
+
+    /// Whether the policy also applies to subdomains of the host
+    pub include_subdomains: bool,
+}
+
+impl Policy {
+    /// Returns when the policy expires, saturating at the latest
+    /// representable time for a huge max-age
+    pub fn expiry(&self) -> DateTime<Utc> {
+        i64::try_from(self.expires_at)
+            .ok()
+            .and_then(|secs| DateTime::from_timestamp(secs, 0))
+            .unwrap_or(DateTime::<Utc>::MAX_UTC)
+    }
+}
+
+/// Hosts known to require HTTPS, stored alongside the session store
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct HstsCache(BTreeMap<String, Policy>);
+
+impl HstsCache {
+    fn path() -> Result<PathBuf> {
+        Ok(get_data_home()?.join("get").join("hsts.json"))
+    }
+
+    pub async fn load() -> Result<Self> {
+        let mut cache: Self = match fs::read(Self::path()?).await {
+            Ok(contents) => serde_json::from_slice(&contents).context("parse HSTS cache")?,
+            Err(err) if err.kind() == ErrorKind::NotFound => Self::default(),
+            Err(err) => return Err(err).context("open HSTS cache"),
+        };
+
+        let now = now();
+        cache.0.retain(|_, policy| policy.expires_at > now);
+
+        Ok(cache)
+    }
+
+    pub async fn save(&self) -> Result<()> {
+        let path = Self::path()?;
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        fs::write(&path, serde_json::to_vec(self)?)
+            .await
+            .context("write HSTS cache")
+    }
+
+    /// Returns the unexpired policies by host
+    pub fn policies(&self) -> &BTreeMap<String, Policy> {
+        &self.0
+    }
+
+    /// Removes the policy for a host, returning whether there was one
+    pub fn remove(&mut self, host: &str) -> bool {
+        self.0.remove(&host.to_ascii_lowercase()).is_some()
+    }
+
+    /// Removes every policy
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    /// Returns whether requests to the host must use HTTPS, because of a
+    /// policy for the host or for a parent domain that includes subdomains
+    pub fn is_secure(&self, host: &str) -> bool {
+        let host = host.to_ascii_lowercase();
+        let now = now();
+
+        let active = |domain: &str| self.0.get(domain).filter(|policy| policy.expires_at > now);
+
+        if active(&host).is_some() {
+            return true;
+        }
+
+        host.match_indices('.').any(|(index, _)| {
+            active(&host[index + 1..]).is_some_and(|policy| policy.include_subdomains)
+        })
+    }
+
+    /// Records a Strict-Transport-Security header value received from the
+    /// host, returning whether the cache changed
+    ///
+    /// A max-age of zero removes the host's policy.
+    pub fn record(&mut self, host: &str, value: &str) -> bool {
+        let Some((max_age, include_subdomains)) = parse(value) else {
+            return false;
+        };
+
+        let host = host.to_ascii_lowercase();
+
+        if max_age == 0 {
+            return self.0.remove(&host).is_some();
+        }
+
+        let policy = Policy {
+            expires_at: now().saturating_add(max_age),
+            include_subdomains,
+        };
+
+        self.0.insert(host, policy);
+        true
+    }
+
+    /// Records the policy sent in a response, if it was received over HTTPS
+    /// from a named host
+    pub async fn record_response(response: &Response) -> Result<()> {
+        let url = response.url();
+
+        let (Some(host), Some(value)) = (
+            url.host_str(),
+            response.headers().get(STRICT_TRANSPORT_SECURITY),
+        ) else {
+            return Ok(());
+        };
+
+        // Policies are ignored over plain HTTP and for IP addresses.
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if url.scheme() != "https" || host.parse::<IpAddr>().is_ok() {
+            return Ok(());
+        }
+
+        // A cache that can't be loaded was already reported when the request
+        // was built, and is replaced rather than left broken.
+        let mut cache = Self::load().await.unwrap_or_default();
+
+        if cache.record(host, value.to_str()?) {
+            cache.save().await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses the max-age and includeSubDomains directives of a
+/// Strict-Transport-Security header value
+fn parse(value: &str) -> Option<(u64, bool)> {
+    let mut max_age = None;
+    let mut include_subdomains = false;
+
+    for directive in value.split(';') {
+        let (name, value) = match directive.split_once('=') {
+            Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+            None => (directive.trim(), None),
+        };
+
+        if name.eq_ignore_ascii_case("max-age") {
+            max_age = Some(value?.parse().ok()?);
+        } else if name.eq_ignore_ascii_case("includeSubDomains") {
+            include_subdomains = true;
+        }
+    }
+
+    Some((max_age?, include_subdomains))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_directives() {
+        assert_eq!(parse("max-age=31536000"), Some((31536000, false)));
+        assert_eq!(
+            parse(r#"max-age="600"; includeSubDomains; preload"#),
+            Some((600, true))
+        );
+        assert_eq!(parse("includeSubDomains"), None);
+        assert_eq!(parse("max-age=soon"), None);
+    }
+
+    #[test]
+    fn upgrade_subdomains() {
+        let mut cache = HstsCache::default();
+
+        assert!(cache.record("Example.com", "max-age=600; includeSubDomains"));
+        assert!(cache.record("other.test", "max-age=600"));
+
+        assert!(cache.is_secure("example.com"));
+        assert!(cache.is_secure("api.example.com"));
+        assert!(cache.is_secure("other.test"));
+        assert!(!cache.is_secure("api.other.test"));
+        assert!(!cache.is_secure("notexample.com"));
+    }
+
+    #[test]
+    fn remove_policy_with_zero_max_age() {
+        let mut cache = HstsCache::default();
+
+        cache.record("example.com", "max-age=600");
+        assert!(cache.record("example.com", "max-age=0"));
+        assert!(!cache.is_secure("example.com"));
+        assert!(!cache.record("example.com", "max-age=0"));
+    }
+
+    #[test]
+    fn saturate_huge_expiry() {
+        let mut cache = HstsCache::default();
+        cache.record("example.com", "max-age=18446744073709551615");
+
+        let policy = &cache.policies()["example.com"];
+        assert_eq!(policy.expiry(), DateTime::<Utc>::MAX_UTC);
+    }
+}
@@ -1,32 +1,239 @@
-use anyhow::{Context, Result};
-use serde_json::{json, Value};
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Number, Value};
 
-use crate::parser::BodyValue;
+use crate::{parser::BodyValue, validate::Problem};
 
+/// A step in the path of a body value, e.g. `[0]` in `tags[0]`
 #[derive(Debug, PartialEq)]
 pub enum PathAccess {
+    /// A key of an object, e.g. `user` or `[user]`
     ObjectKey(String),
+
+    /// An index into an array, e.g. `[0]` or `.0`
     ArrayIndex(u32),
+
+    /// `[]`, appending to an array
     ArrayEnd,
 }
 
+/// The type to convert a body value to, given as a suffix such as `::int`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ValueType {
+    /// A string, e.g. to keep `5` as one with `--infer-types`
+    Str,
+
+    /// An integer
+    Int,
+
+    /// A floating point number
+    Float,
+
+    /// `true` or `false`
+    Bool,
+
+    /// A comma-separated list of strings, or of the numbers and booleans
+    /// they obviously represent with `--infer-types`
+    List,
+}
+
+impl ValueType {
+    /// Splits a type suffix off a value, e.g. `5::int`
+    ///
+    /// Only the last suffix is split off, so `a::int::str` is the string
+    /// `a::int`.
+    pub(crate) fn split(value: &str) -> (&str, Option<Self>) {
+        let Some((rest, suffix)) = value.rsplit_once("::") else {
+            return (value, None);
+        };
+
+        let value_type = match suffix {
+            "str" => Self::Str,
+            "int" => Self::Int,
+            "float" => Self::Float,
+            "bool" => Self::Bool,
+            "list" => Self::List,
+            _ => return (value, None),
+        };
+
+        (rest, Some(value_type))
+    }
+
+    /// Converts a value to the type, adding a warning to `warnings` for each
+    /// list item whose type is inferred
+    fn convert(
+        self,
+        value: &str,
+        path: &[PathAccess],
+        infer_types: bool,
+        warnings: &mut Vec<Problem>,
+    ) -> Result<Value> {
+        Ok(match self {
+            Self::Str => json!(value),
+
+            Self::Int => match value.parse::<i64>() {
+                Ok(int) => json!(int),
+                Err(_) => bail!("expected an integer, got {:?}", value),
+            },
+
+            Self::Float => match value.parse::<f64>().ok().and_then(Number::from_f64) {
+                Some(float) => Value::Number(float),
+                None => bail!("expected a number, got {:?}", value),
+            },
+
+            Self::Bool => match value {
+                "true" => json!(true),
+                "false" => json!(false),
+                _ => bail!("expected true or false, got {:?}", value),
+            },
+
+            Self::List if value.is_empty() => json!([]),
+            Self::List => Value::Array(
+                value
+                    .split(',')
+                    .enumerate()
+                    .map(|(index, item)| {
+                        string_or_inferred(
+                            item.trim(),
+                            &format!("{}[{}]", describe(path), index),
+                            "use := with a JSON array to send strings",
+                            infer_types,
+                            warnings,
+                        )
+                    })
+                    .collect(),
+            ),
+        })
+    }
+}
+
 pub fn build(values: &[BodyValue]) -> Result<String> {
+    Ok(build_body(values, false)?.0)
+}
+
+/// Builds the body, sending string values that are obviously numbers or
+/// booleans as such, with a warning for each
+///
+/// Numbers are only inferred from plain decimals, so values such as `007`,
+/// `1e3` or `+1` stay strings.
+pub fn build_inferring_types(values: &[BodyValue]) -> Result<(String, Vec<Problem>)> {
+    build_body(values, true)
+}
+
+fn build_body(values: &[BodyValue], infer_types: bool) -> Result<(String, Vec<Problem>)> {
     let mut root = json!(null);
+    let mut warnings = vec![];
 
     for value in values {
         match value {
             BodyValue::String { path, value } => {
-                put_value(&mut root, path, json!(value))?;
+                let value = string_or_inferred(
+                    value,
+                    &describe(path),
+                    "add ::str to send a string",
+                    infer_types,
+                    &mut warnings,
+                );
+                put_value(&mut root, path, value)?;
+            }
+
+            BodyValue::Typed {
+                path,
+                value,
+                value_type,
+            } => {
+                let value = value_type
+                    .convert(value, path, infer_types, &mut warnings)
+                    .with_context(|| format!("Invalid value for {}", describe(path)))?;
+                put_value(&mut root, path, value)?;
             }
 
-            BodyValue::JSON { path, value } => {
+            BodyValue::Json { path, value } => {
                 let value = serde_json::from_str(value)?;
                 put_value(&mut root, path, value)?;
             }
         }
     }
 
-    Ok(root.to_string())
+    Ok((root.to_string(), warnings))
+}
+
+/// Returns a string value, or the number or boolean it obviously represents
+/// if inferring types, with a warning that describes it and how to keep it a
+/// string
+fn string_or_inferred(
+    value: &str,
+    description: &str,
+    hint: &str,
+    infer_types: bool,
+    warnings: &mut Vec<Problem>,
+) -> Value {
+    let Some(inferred) = infer_types.then(|| infer(value)).flatten() else {
+        return json!(value);
+    };
+
+    warnings.push(Problem::Warning(format!(
+        "{}: sending {} as {}, {}",
+        description,
+        value,
+        if inferred.is_boolean() {
+            "a boolean"
+        } else {
+            "a number"
+        },
+        hint
+    )));
+
+    inferred
+}
+
+/// Returns the number or boolean a value obviously represents, if any
+fn infer(value: &str) -> Option<Value> {
+    match value {
+        "true" => return Some(json!(true)),
+        "false" => return Some(json!(false)),
+        _ => {}
+    }
+
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    let (whole, fraction) = match digits.split_once('.') {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (digits, None),
+    };
+
+    let is_digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+
+    if !is_digits(whole) || (whole.len() > 1 && whole.starts_with('0')) {
+        return None;
+    }
+
+    match fraction {
+        None => value.parse::<i64>().ok().map(|int| json!(int)),
+        Some(fraction) if is_digits(fraction) => value
+            .parse::<f64>()
+            .ok()
+            .and_then(Number::from_f64)
+            .map(Value::Number),
+        Some(_) => None,
+    }
+}
+
+/// Describes a path for messages, e.g. `user.tags[0]`
+fn describe(path: &[PathAccess]) -> String {
+    let mut description = String::new();
+
+    for access in path {
+        match access {
+            PathAccess::ObjectKey(key) if description.is_empty() => description.push_str(key),
+            PathAccess::ObjectKey(key) => {
+                description.push('.');
+                description.push_str(key);
+            }
+            PathAccess::ArrayIndex(index) => description.push_str(&format!("[{}]", index)),
+            PathAccess::ArrayEnd => description.push_str("[]"),
+        }
+    }
+
+    description
 }
 
 fn put_value(root: &mut Value, path: &[PathAccess], value: Value) -> Result<()> {
@@ -73,3 +280,113 @@ fn put_value(root: &mut Value, path: &[PathAccess], value: Value) -> Result<()>
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(key: &str, value: &str) -> BodyValue {
+        BodyValue::String {
+            path: vec![PathAccess::ObjectKey(key.to_string())],
+            value: value.to_string(),
+        }
+    }
+
+    fn typed(key: &str, value: &str, value_type: ValueType) -> BodyValue {
+        BodyValue::Typed {
+            path: vec![PathAccess::ObjectKey(key.to_string())],
+            value: value.to_string(),
+            value_type,
+        }
+    }
+
+    #[test]
+    fn split_type_suffixes() {
+        assert_eq!(ValueType::split("5::int"), ("5", Some(ValueType::Int)));
+        assert_eq!(
+            ValueType::split("a::int::str"),
+            ("a::int", Some(ValueType::Str))
+        );
+        assert_eq!(ValueType::split("std::io"), ("std::io", None));
+        assert_eq!(ValueType::split("5"), ("5", None));
+    }
+
+    #[test]
+    fn convert_typed_values() {
+        let body: Value = serde_json::from_str(
+            &build(&[
+                typed("count", "5", ValueType::Int),
+                typed("ratio", "0.5", ValueType::Float),
+                typed("flag", "true", ValueType::Bool),
+                typed("ids", "1, 2,x", ValueType::List),
+                typed("none", "", ValueType::List),
+                typed("zip", "007", ValueType::Str),
+            ])
+            .unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            body,
+            json!({"count": 5, "ratio": 0.5, "flag": true, "ids": ["1", "2", "x"], "none": [], "zip": "007"})
+        );
+    }
+
+    #[test]
+    fn infer_list_item_types() {
+        let (body, warnings) =
+            build_inferring_types(&[typed("ids", "1,true,x", ValueType::List)]).unwrap();
+
+        assert_eq!(body, r#"{"ids":[1,true,"x"]}"#);
+        assert_eq!(
+            warnings,
+            vec![
+                Problem::Warning(
+                    "ids[0]: sending 1 as a number, use := with a JSON array to send strings"
+                        .to_string()
+                ),
+                Problem::Warning(
+                    "ids[1]: sending true as a boolean, use := with a JSON array to send strings"
+                        .to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn reject_invalid_typed_values() {
+        let err = build(&[typed("count", "five", ValueType::Int)]).unwrap_err();
+
+        assert_eq!(err.to_string(), "Invalid value for count");
+        assert_eq!(
+            err.root_cause().to_string(),
+            r#"expected an integer, got "five""#
+        );
+        assert!(build(&[typed("flag", "yes", ValueType::Bool)]).is_err());
+        assert!(build(&[typed("ratio", "NaN", ValueType::Float)]).is_err());
+    }
+
+    #[test]
+    fn infer_obvious_types() {
+        let values = [
+            string("count", "5"),
+            string("ratio", "-0.5"),
+            string("flag", "false"),
+            string("zip", "007"),
+            string("big", "1e3"),
+            string("name", "get"),
+        ];
+
+        assert_eq!(
+            build(&values).unwrap(),
+            r#"{"big":"1e3","count":"5","flag":"false","name":"get","ratio":"-0.5","zip":"007"}"#
+        );
+
+        let (body, warnings) = build_inferring_types(&values).unwrap();
+        assert_eq!(
+            body,
+            r#"{"big":"1e3","count":5,"flag":false,"name":"get","ratio":-0.5,"zip":"007"}"#
+        );
+        assert_eq!(warnings.len(), 3);
+    }
+}
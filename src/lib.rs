@@ -0,0 +1,79 @@
+//! Build and send HTTP requests from concise, command-line style input
+//!
+//! This is the library behind the `get` command. It parses request
+//! components such as `page==2`, `X-Team:core` and `user.name=get` into a
+//! [`ParsedRequest`], expands URL shorthand such as `:8080/users` and config
+//! aliases with [`URLBuilder`], and combines them with the [`Config`] and the
+//! saved [`Session`] for the host in a [`RequestBuilder`]:
+//!
+//! ```no_run
+//! use std::collections::BTreeMap;
+//!
+//! use get::{Config, InputOptions, ParsedRequest, Renderer, RequestBuilder};
+//! use reqwest::Method;
+//!
+//! # async fn example() -> Result<(), get::Error> {
+//! let config = Config::load().await?;
+//!
+//! let variables = BTreeMap::new();
+//! let mut renderer = Renderer::new(&variables);
+//! let parsed = ParsedRequest::from_inputs(&["page==2", "user.name=get"], &mut renderer)?;
+//! renderer.finish()?;
+//!
+//! let options = InputOptions::default();
+//! let mut request =
+//!     RequestBuilder::from_input("api.example.com/users", &config, Some(&variables), &options)
+//!         .await?
+//!         .add_query(&parsed.query)
+//!         .merge_headers(parsed.headers)?
+//!         .add_data(&parsed.body, None)?;
+//!
+//! let response = request.send(Method::POST).await?;
+//! println!("{}", response.status());
+//!
+//! for warning in request.take_warnings() {
+//!     eprintln!("{}", warning);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Failures are reported as an [`Error`], categorised by what went wrong, and
+//! problems that don't stop the request as [`Problem`]s. [`run`] is the `get`
+//! command itself.
+
+#![warn(missing_docs)]
+
+mod auth;
+mod cli;
+mod config;
+mod digest;
+mod encryption;
+mod error;
+mod format;
+mod host_pattern;
+mod hsts;
+mod json_builder;
+mod netrc;
+mod oauth2;
+mod parser;
+mod request_builder;
+mod secret;
+mod session;
+mod signing;
+mod sigv4;
+mod template;
+mod transport;
+mod url_builder;
+mod validate;
+
+pub use cli::run;
+pub use config::Config;
+pub use error::Error;
+pub use json_builder::{PathAccess, ValueType};
+pub use parser::{BodyValue, ParsedRequest};
+pub use request_builder::{InputOptions, RequestBuilder};
+pub use session::Session;
+pub use template::Renderer;
+pub use url_builder::URLBuilder;
+pub use validate::Problem;
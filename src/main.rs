@@ -1,14 +1,6 @@
-use anyhow::Result;
-
-mod cli;
-mod config;
-mod json_builder;
-mod parser;
-mod request_builder;
-mod session;
-mod url_builder;
+use std::process::ExitCode;
 
 #[tokio::main]
-async fn main() -> Result<()> {
-    cli::run().await
+async fn main() -> ExitCode {
+    get::run().await
 }
@@ -0,0 +1,213 @@
+use std::{env, io::ErrorKind, path::PathBuf};
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use tokio::fs;
+
+use crate::{
+    auth::Auth,
+    secret::{expand_home, Secret},
+};
+
+/// Whether credentials are looked up in the .netrc file
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum NetrcMode {
+    /// Never read the .netrc file
+    Ignore,
+
+    /// Use credentials from the .netrc file if there are any for the host
+    #[default]
+    Optional,
+
+    /// Fail if the .netrc file has no credentials for the host
+    Required,
+}
+
+/// A login and password for a machine in a .netrc file
+#[derive(Debug, PartialEq)]
+pub struct Entry {
+    pub machine: Option<String>,
+    pub login: Option<String>,
+    pub password: Option<String>,
+}
+
+impl Entry {
+    /// Returns the entry's credentials, if it has a login
+    ///
+    /// A missing password is sent as an empty one rather than prompted for,
+    /// so that a .netrc file never makes a script wait for input.
+    pub fn auth(&self) -> Option<Auth> {
+        Some(Auth::Basic {
+            username: self.login.clone()?,
+            password: Some(Secret::from(self.password.as_deref().unwrap_or_default())),
+        })
+    }
+}
+
+/// The entries of a .netrc file
+#[derive(Debug, Default)]
+pub struct Netrc(Vec<Entry>);
+
+impl Netrc {
+    /// Loads the .netrc file at the given path, `$NETRC`, or `~/.netrc`
+    ///
+    /// A missing default file is treated as empty, while a missing file at
+    /// the given path is an error, so that a typo isn't silently ignored.
+    pub async fn load(path: Option<&str>) -> Result<Self> {
+        let (path, explicit) = match path {
+            Some(path) => (expand_home(path)?, true),
+            None => (default_path()?, false),
+        };
+
+        match fs::read_to_string(&path).await {
+            Ok(contents) => Ok(Self::parse(&contents)),
+            Err(err) if err.kind() == ErrorKind::NotFound && !explicit => Ok(Self::default()),
+            Err(err) => Err(err).with_context(|| format!("read netrc file {:?}", path)),
+        }
+    }
+
+    /// Parses the contents of a .netrc file
+    ///
+    /// Macro definitions are skipped, and tokens we don't use are ignored.
+    pub fn parse(contents: &str) -> Self {
+        let mut entries: Vec<Entry> = vec![];
+        let mut lines = contents.lines();
+
+        while let Some(line) = lines.next() {
+            let mut tokens = line.split_whitespace();
+
+            while let Some(token) = tokens.next() {
+                match token {
+                    "machine" => entries.push(Entry {
+                        machine: tokens.next().map(str::to_string),
+                        login: None,
+                        password: None,
+                    }),
+
+                    "default" => entries.push(Entry {
+                        machine: None,
+                        login: None,
+                        password: None,
+                    }),
+
+                    "login" => {
+                        if let Some(entry) = entries.last_mut() {
+                            entry.login = tokens.next().map(str::to_string);
+                        }
+                    }
+
+                    "password" => {
+                        if let Some(entry) = entries.last_mut() {
+                            entry.password = tokens.next().map(str::to_string);
+                        }
+                    }
+
+                    "account" => {
+                        tokens.next();
+                    }
+
+                    // A macro definition runs until the next empty line.
+                    "macdef" => {
+                        for line in lines.by_ref() {
+                            if line.trim().is_empty() {
+                                break;
+                            }
+                        }
+
+                        break;
+                    }
+
+                    _ => {}
+                }
+            }
+        }
+
+        Self(entries)
+    }
+
+    /// Returns the entry for the given host, falling back to the default entry
+    pub fn find(&self, host: &str) -> Option<&Entry> {
+        self.0
+            .iter()
+            .find(|entry| entry.machine.as_deref() == Some(host))
+            .or_else(|| self.0.iter().find(|entry| entry.machine.is_none()))
+    }
+}
+
+fn default_path() -> Result<PathBuf> {
+    match env::var("NETRC") {
+        Ok(path) => Ok(PathBuf::from(path)),
+        Err(_) => expand_home("~/.netrc"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NETRC: &str = "machine api.example.com login alice password s3cret
+
+machine other.example.com
+  login bob
+  account ignored
+  password hunter2
+
+macdef init
+machine evil.example.com login mallory password nope
+
+default login anonymous password guest
+";
+
+    #[test]
+    fn find_machine() {
+        let netrc = Netrc::parse(NETRC);
+
+        assert_eq!(
+            netrc.find("api.example.com"),
+            Some(&Entry {
+                machine: Some("api.example.com".to_string()),
+                login: Some("alice".to_string()),
+                password: Some("s3cret".to_string()),
+            })
+        );
+
+        let entry = netrc.find("other.example.com").unwrap();
+        assert_eq!(entry.login.as_deref(), Some("bob"));
+        assert_eq!(entry.password.as_deref(), Some("hunter2"));
+    }
+
+    #[test]
+    fn skip_macros() {
+        let netrc = Netrc::parse(NETRC);
+        assert_eq!(netrc.find("evil.example.com").unwrap().machine, None);
+    }
+
+    #[test]
+    fn fall_back_to_default() {
+        let netrc = Netrc::parse(NETRC);
+        let entry = netrc.find("unknown.example.com").unwrap();
+
+        assert_eq!(entry.machine, None);
+        assert_eq!(entry.login.as_deref(), Some("anonymous"));
+    }
+
+    #[tokio::test]
+    async fn reject_missing_explicit_file() {
+        let err = Netrc::load(Some("/nonexistent/.netrc")).await.unwrap_err();
+        assert_eq!(err.to_string(), r#"read netrc file "/nonexistent/.netrc""#);
+    }
+
+    #[test]
+    fn use_empty_password_for_login_only_entry() {
+        let netrc = Netrc::parse("machine example.com login alice\nmachine other.com");
+
+        assert_eq!(
+            netrc.find("example.com").and_then(Entry::auth),
+            Some(Auth::Basic {
+                username: "alice".to_string(),
+                password: Some("".into()),
+            })
+        );
+        assert_eq!(netrc.find("other.com").and_then(Entry::auth), None);
+    }
+}
@@ -4,48 +4,97 @@ use anyhow::{bail, Result};
 use http::{HeaderMap, HeaderName, HeaderValue};
 use nom::{
     branch::alt,
-    bytes::complete::{tag, take_while1},
-    character::complete::u32,
-    combinator::value,
-    multi::many0,
-    sequence::{delimited, preceded, separated_pair},
-    IResult,
+    bytes::complete::{is_not, tag, take, take_while1},
+    character::complete::{one_of, u32},
+    combinator::{all_consuming, cut, eof, not, opt, peek, recognize, value, verify},
+    error::{context, ErrorKind, ParseError, VerboseError, VerboseErrorKind},
+    multi::{fold_many0, many0},
+    sequence::{delimited, preceded, separated_pair, terminated},
+    IResult, Offset,
 };
+use serde_json::error::Category;
 
-use crate::json_builder::PathAccess;
+use crate::{
+    json_builder::{PathAccess, ValueType},
+    template::Renderer,
+};
 
+/// The query parameters, headers and body values given as request components
+///
+/// Components are `name==value` for a query parameter, `Name:value` for a
+/// header, `Name;` for a header with an empty value, `Name:` to remove a
+/// header other than Host and Accept, and `path=value` or `path:=json` for a
+/// body value, where the path is a sequence of keys and indexes such as
+/// `user.tags[0]`. A body value may end in `::str`, `::int`, `::float`,
+/// `::bool` or `::list` to send it as that type.
 #[derive(Debug)]
+#[non_exhaustive]
 pub struct ParsedRequest {
+    /// Query parameters, given as `name==value`
     pub query: Vec<(String, String)>,
+
+    /// Headers, given as `Name:value` or `Name;`
     pub headers: HeaderMap,
+
+    /// Headers to leave out of the request, given as `Name:`
+    pub removed_headers: Vec<HeaderName>,
+
+    /// Body values, in the order given
     pub body: Vec<BodyValue>,
 }
 
 impl ParsedRequest {
-    pub fn from_inputs<T>(inputs: &[T]) -> Result<Self>
+    /// Parses the inputs, rendering placeholders in header, query and body values
+    pub fn from_inputs<T>(inputs: &[T], renderer: &mut Renderer) -> Result<Self>
     where
         T: AsRef<str>,
     {
         let mut query = vec![];
         let mut headers = HeaderMap::new();
+        let mut removed_headers = vec![];
         let mut body = vec![];
 
         for input in inputs {
-            let component = parse_component(input.as_ref())?;
+            let input = input.as_ref();
+            let component = match parse_component(input) {
+                Ok(component) => component,
+                Err(diagnostic) => bail!("{}", diagnostic.report(input)),
+            };
 
             match component {
                 RequestComponent::QueryParam { name, value } => {
-                    query.push((name, value));
+                    query.push((name, renderer.render(&value)));
                 }
 
                 RequestComponent::Header { key, value } => {
                     let key = HeaderName::from_str(&key)?;
-                    let value = HeaderValue::from_str(&value)?;
+                    let value = HeaderValue::from_str(&renderer.render(&value))?;
                     headers.append(key, value);
                 }
 
+                RequestComponent::RemoveHeader { key } => {
+                    removed_headers.push(HeaderName::from_str(&key)?);
+                }
+
+                RequestComponent::BodyValue(BodyValue::Json { path, value }) => {
+                    let start = input.len() - value.len();
+                    let value = renderer.render(&value);
+
+                    // Values with unresolved placeholders are reported by the
+                    // renderer instead
+                    if !renderer.has_errors() {
+                        if let Err(error) = serde_json::from_str::<serde_json::Value>(&value) {
+                            let component = format!("{}{}", &input[..start], value);
+                            let diagnostic = Diagnostic::from_json_error(start, &value, &error);
+                            bail!("{}", diagnostic.report(&component));
+                        }
+                    }
+
+                    body.push(BodyValue::Json { path, value });
+                }
+
                 RequestComponent::BodyValue(value) => {
-                    body.push(value);
+                    body.push(value.render(renderer));
                 }
             }
         }
@@ -53,81 +102,320 @@ impl ParsedRequest {
         Ok(Self {
             query,
             headers,
+            removed_headers,
             body,
         })
     }
 }
 
+/// A value to set at a path in the JSON request body
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum BodyValue {
+    /// A string value, given as `path=value`
     String {
+        /// Where to set the value
+        path: Vec<PathAccess>,
+        /// The value
+        value: String,
+    },
+
+    /// A string value with a type suffix such as `::int`, converted when the
+    /// body is built
+    Typed {
+        /// Where to set the value
         path: Vec<PathAccess>,
+        /// The value, without its suffix
         value: String,
+        /// The type to convert the value to
+        value_type: ValueType,
     },
 
-    JSON {
+    /// A JSON value, given as `path:=json`
+    Json {
+        /// Where to set the value
         path: Vec<PathAccess>,
+        /// The JSON text of the value
         value: String,
     },
 }
 
+impl BodyValue {
+    fn render(self, renderer: &mut Renderer) -> Self {
+        match self {
+            Self::String { path, value } => Self::String {
+                value: renderer.render(&value),
+                path,
+            },
+            Self::Typed {
+                path,
+                value,
+                value_type,
+            } => Self::Typed {
+                value: renderer.render(&value),
+                path,
+                value_type,
+            },
+            Self::Json { path, value } => Self::Json {
+                value: renderer.render(&value),
+                path,
+            },
+        }
+    }
+}
+
 #[derive(Debug)]
 enum RequestComponent {
     QueryParam { name: String, value: String },
     Header { key: String, value: String },
+    RemoveHeader { key: String },
     BodyValue(BodyValue),
 }
 
-fn parse_component(input: &str) -> Result<RequestComponent> {
-    match alt((query_param, body, header))(input) {
-        Ok((remainder, component)) => {
-            if remainder.is_empty() {
-                Ok(component)
-            } else {
-                bail!("Remainder found in request component")
+type ParseResult<'a, T> = IResult<&'a str, T, VerboseError<&'a str>>;
+
+const SEPARATOR: &str =
+    "a separator: '==' for a query parameter, '=' or ':=' for a body value, or ':' for a header";
+const BODY_SEPARATOR: &str = "'=' or ':=' after the key";
+const CLOSING_BRACKET: &str = "a closing ']'";
+const KEY_AFTER_DOT: &str = "a key or numeric index after '.'";
+const ESCAPED_CHARACTER: &str = "a character to escape after '\\'";
+const HEADER_NAME: &str = "a header name before the separator";
+const HEADER_NAME_CHARACTER: &str = "a letter, digit, '-' or '_' in the header name";
+const END_OF_EMPTY_HEADER: &str = "nothing after ';'";
+
+/// Parses a component as a query parameter, a body value or a header, in
+/// that order
+///
+/// If none of them match, the error that got furthest into the component is
+/// diagnosed, unless one of them had already committed to the component with
+/// a `cut`.
+fn parse_component(input: &str) -> Result<RequestComponent, Diagnostic> {
+    let parsers: [fn(&str) -> ParseResult<'_, RequestComponent>; 3] = [query_param, body, header];
+    let mut furthest: Option<VerboseError<&str>> = None;
+
+    for parser in parsers {
+        let error = match all_consuming(parser)(input) {
+            Ok((_, component)) => return Ok(component),
+            Err(nom::Err::Failure(error)) => return Err(Diagnostic::from_error(input, &error)),
+            Err(nom::Err::Error(error)) => error,
+            Err(nom::Err::Incomplete(_)) => {
+                unreachable!("complete parsers don't ask for more input")
+            }
+        };
+
+        if furthest
+            .as_ref()
+            .is_none_or(|furthest| error_offset(input, &error) > error_offset(input, furthest))
+        {
+            furthest = Some(error);
+        }
+    }
+
+    let error = furthest.unwrap_or_else(|| VerboseError::from_error_kind(input, ErrorKind::Alt));
+    Err(Diagnostic::from_error(input, &error))
+}
+
+/// The byte offset of the innermost error
+fn error_offset(input: &str, error: &VerboseError<&str>) -> usize {
+    error
+        .errors
+        .first()
+        .map_or(0, |(remainder, _)| input.offset(remainder))
+}
+
+/// Explains why a request component is invalid
+#[derive(Debug, PartialEq)]
+struct Diagnostic {
+    /// The byte offset at which the component went wrong
+    offset: usize,
+
+    /// What went wrong at the offset
+    message: String,
+
+    /// A likely fix
+    hint: Option<String>,
+}
+
+impl Diagnostic {
+    fn new(offset: usize, message: String) -> Self {
+        Self {
+            offset,
+            message,
+            hint: None,
+        }
+    }
+
+    fn hint(mut self, hint: &str) -> Self {
+        self.hint = Some(hint.to_string());
+        self
+    }
+
+    /// Diagnoses a parse error from its innermost error and context
+    fn from_error(input: &str, error: &VerboseError<&str>) -> Self {
+        let offset = error_offset(input, error);
+        let expected = error
+            .errors
+            .iter()
+            .find_map(|(_, kind)| match kind {
+                VerboseErrorKind::Context(context) => Some(*context),
+                _ => None,
+            })
+            .unwrap_or(SEPARATOR);
+        let diagnostic = Self::new(offset, format!("expected {}", expected));
+
+        match expected {
+            SEPARATOR if input.is_empty() => diagnostic.hint("remove the empty argument"),
+            SEPARATOR if input.starts_with('/') => {
+                diagnostic.hint("paths go in the URL, e.g. example.com/path")
             }
+            SEPARATOR => diagnostic.hint(
+                "quote values containing spaces so that they are passed as one argument, e.g. 'name=some value'",
+            ),
+            BODY_SEPARATOR if input[offset..].starts_with(':') => {
+                diagnostic.hint("escape colons in a key as '\\:'")
+            }
+            CLOSING_BRACKET => diagnostic.hint("escape brackets in a key as '\\[' and '\\]'"),
+            KEY_AFTER_DOT => diagnostic.hint("escape dots in a key as '\\.'"),
+            ESCAPED_CHARACTER => diagnostic.hint("use '\\\\' for a literal backslash"),
+            HEADER_NAME_CHARACTER => match input[offset..].chars().next() {
+                Some(' ') => diagnostic
+                    .hint("header names can't contain spaces; for a body value use 'name=value'"),
+                Some(c) => {
+                    diagnostic.hint(&format!("escape it as '\\{}' if it's part of the name", c))
+                }
+                None => diagnostic,
+            },
+            END_OF_EMPTY_HEADER => diagnostic.hint("use 'Name:value' for a header with a value"),
+            _ => diagnostic,
         }
+    }
+
+    /// Diagnoses an invalid `:=` value, which starts at `start` in the
+    /// component
+    ///
+    /// Running out of input is reported at the end of the value rather than
+    /// at its last character.
+    fn from_json_error(start: usize, value: &str, error: &serde_json::Error) -> Self {
+        let line_start: usize = value
+            .split_inclusive('\n')
+            .take(error.line().saturating_sub(1))
+            .map(str::len)
+            .sum();
+        let mut offset = match error.classify() {
+            Category::Eof => value.len(),
+            _ => (line_start + error.column().saturating_sub(1)).min(value.len()),
+        };
+
+        while !value.is_char_boundary(offset) {
+            offset -= 1;
+        }
+
+        let message = error.to_string();
+        let position = format!(" at line {} column {}", error.line(), error.column());
+        let message = message.strip_suffix(&position).unwrap_or(&message);
+        let diagnostic = Self::new(start + offset, format!("invalid JSON: {}", message));
+
+        if value.starts_with(|c: char| c.is_ascii_alphabetic()) || value.is_empty() {
+            diagnostic.hint("use '=' instead of ':=' to send it as a string")
+        } else {
+            diagnostic
+        }
+    }
+
+    /// Formats the diagnostic, quoting the component with a caret under the
+    /// failing column
+    fn report(&self, input: &str) -> String {
+        let column = input[..self.offset].chars().count();
+
+        let mut report = format!(
+            "Invalid request component '{}' at column {}\n  {}\n  {}^ {}",
+            input,
+            column + 1,
+            input,
+            " ".repeat(column),
+            self.message
+        );
 
-        Err(_) => {
-            bail!("Invalid request component")
+        if let Some(hint) = &self.hint {
+            report.push_str(&format!("\n  hint: {}", hint));
         }
+
+        report
     }
 }
 
-fn body(input: &str) -> IResult<&str, RequestComponent> {
+fn body(input: &str) -> ParseResult<'_, RequestComponent> {
     let mut path: Vec<PathAccess> = vec![];
 
     let (input, mut keys) = many0(alt((array_index, object_key, array_end)))(input)?;
 
     path.append(&mut keys);
 
-    let body = match alt((value(true, tag(":=")), value(false, tag("="))))(input)? {
-        (value, true) => BodyValue::JSON {
+    let body = match context(
+        BODY_SEPARATOR,
+        alt((value(true, tag(":=")), value(false, tag("=")))),
+    )(input)?
+    {
+        (value, true) => BodyValue::Json {
             path,
             value: value.to_string(),
         },
-        (value, false) => BodyValue::String {
-            path,
-            value: value.to_string(),
+        (value, false) => match ValueType::split(value) {
+            (value, Some(value_type)) => BodyValue::Typed {
+                path,
+                value: value.to_string(),
+                value_type,
+            },
+            (value, None) => BodyValue::String {
+                path,
+                value: value.to_string(),
+            },
         },
     };
 
     Ok(("", RequestComponent::BodyValue(body)))
 }
 
-fn object_key(input: &str) -> IResult<&str, PathAccess> {
-    let raw_object_key = take_while1(|c| c != '.' && c != '[' && c != '=' && c != ':');
-
+fn object_key(input: &str) -> ParseResult<'_, PathAccess> {
     let (remainder, key) = alt((
-        delimited(tag("["), take_while1(|c| c != ']'), tag("]")),
-        preceded(tag("."), &raw_object_key),
-        &raw_object_key,
+        delimited(
+            tag("["),
+            escaped(is_not("]\\")),
+            cut(context(CLOSING_BRACKET, tag("]"))),
+        ),
+        preceded(
+            tag("."),
+            cut(context(KEY_AFTER_DOT, escaped(is_not(".[=:\\")))),
+        ),
+        escaped(is_not(".[=:\\")),
     ))(input)?;
 
-    Ok((remainder, PathAccess::ObjectKey(key.to_string())))
+    Ok((remainder, PathAccess::ObjectKey(key)))
+}
+
+/// Parses a non-empty run of `normal` input, where a backslash escapes the
+/// next character so that it is taken literally, e.g. `a\.b` for `a.b`
+fn escaped<'a>(
+    normal: impl FnMut(&'a str) -> ParseResult<'a, &'a str>,
+) -> impl FnMut(&'a str) -> ParseResult<'a, String> {
+    let run = fold_many0(
+        alt((normal, preceded(tag("\\"), take(1usize)))),
+        String::new,
+        |mut run, part| {
+            run.push_str(part);
+            run
+        },
+    );
+
+    // The run only stops at a backslash when there's nothing left to escape
+    verify(
+        terminated(run, context(ESCAPED_CHARACTER, not(tag("\\")))),
+        |run: &String| !run.is_empty(),
+    )
 }
 
-fn array_index(input: &str) -> IResult<&str, PathAccess> {
+fn array_index(input: &str) -> ParseResult<'_, PathAccess> {
     let (remainder, index) = alt((
         delimited(tag("["), u32, tag("]")),
         preceded(tag("."), u32),
@@ -137,67 +425,116 @@ fn array_index(input: &str) -> IResult<&str, PathAccess> {
     Ok((remainder, PathAccess::ArrayIndex(index)))
 }
 
-fn array_end(input: &str) -> IResult<&str, PathAccess> {
+fn array_end(input: &str) -> ParseResult<'_, PathAccess> {
     let (remainder, _) = tag("[]")(input)?;
     Ok((remainder, PathAccess::ArrayEnd))
 }
 
-fn query_param(input: &str) -> IResult<&str, RequestComponent> {
-    let (remainder, (name, value)) =
-        separated_pair(query_param_key, tag("=="), query_param_value)(input)?;
+fn query_param(input: &str) -> ParseResult<'_, RequestComponent> {
+    let (remainder, (name, value)) = separated_pair(
+        query_param_key,
+        context(SEPARATOR, tag("==")),
+        query_param_value,
+    )(input)?;
 
     Ok((
         remainder,
         RequestComponent::QueryParam {
-            name: name.to_string(),
+            name,
             value: value.to_string(),
         },
     ))
 }
 
-fn query_param_key(input: &str) -> IResult<&str, &str> {
-    take_while1(|c| c != '=')(input)
+fn query_param_key(input: &str) -> ParseResult<'_, String> {
+    escaped(is_not("=\\"))(input)
 }
 
-fn query_param_value(input: &str) -> IResult<&str, &str> {
+fn query_param_value(input: &str) -> ParseResult<'_, &str> {
     take_while1(|_| true)(input)
 }
 
-fn header(input: &str) -> IResult<&str, RequestComponent> {
+/// Parses `Name:value`, `Name;` for an empty value, or `Name:` to remove the
+/// header
+fn header(input: &str) -> ParseResult<'_, RequestComponent> {
+    alt((header_with_value, empty_header, header_removal))(input)
+}
+
+fn header_with_value(input: &str) -> ParseResult<'_, RequestComponent> {
     let (remainder, (name, value)) = separated_pair(header_name, tag(":"), header_value)(input)?;
 
     Ok((
         remainder,
         RequestComponent::Header {
-            key: name.to_string(),
+            key: name,
             value: value.to_string(),
         },
     ))
 }
 
-fn header_name(input: &str) -> IResult<&str, &str> {
-    take_while1(|c| {
-        ('A'..'Z').contains(&c)
-            || ('a'..'z').contains(&c)
-            || ('0'..'9').contains(&c)
-            || c == '_'
-            || c == '-'
-    })(input)
+fn empty_header(input: &str) -> ParseResult<'_, RequestComponent> {
+    let (remainder, name) = terminated(
+        header_name,
+        preceded(tag(";"), cut(context(END_OF_EMPTY_HEADER, eof))),
+    )(input)?;
+
+    Ok((
+        remainder,
+        RequestComponent::Header {
+            key: name,
+            value: String::new(),
+        },
+    ))
+}
+
+fn header_removal(input: &str) -> ParseResult<'_, RequestComponent> {
+    let (remainder, name) = terminated(header_name, tag(":"))(input)?;
+
+    Ok((remainder, RequestComponent::RemoveHeader { key: name }))
 }
 
-fn header_value(input: &str) -> IResult<&str, &str> {
+/// Parses the header name before a ':' or ';' separator
+///
+/// Once a separator is found the component can only be a header, so an
+/// invalid name is a failure rather than an error.
+fn header_name(input: &str) -> ParseResult<'_, String> {
+    let (remainder, name) = terminated(
+        recognize(opt(escaped(is_not(":;\\")))),
+        context(SEPARATOR, peek(one_of(":;"))),
+    )(input)?;
+
+    let (_, name) = cut(preceded(
+        context(HEADER_NAME, not(eof)),
+        context(
+            HEADER_NAME_CHARACTER,
+            all_consuming(escaped(take_while1(|c: char| {
+                c.is_ascii_alphanumeric() || c == '_' || c == '-'
+            }))),
+        ),
+    ))(name)?;
+
+    Ok((remainder, name))
+}
+
+fn header_value(input: &str) -> ParseResult<'_, &str> {
     take_while1(|_| true)(input)
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeMap;
+
     use crate::json_builder;
 
     use super::*;
 
+    fn from_inputs<T: AsRef<str>>(inputs: &[T]) -> Result<ParsedRequest> {
+        ParsedRequest::from_inputs(inputs, &mut Renderer::new(&BTreeMap::new()))
+    }
+
     #[test]
     fn parse_simple_header() {
-        let request = ParsedRequest::from_inputs(&["foo:bar"]).unwrap();
+        let request = from_inputs(&["foo:bar"]).unwrap();
 
         let headers = vec![(
             HeaderName::from_str("foo").unwrap(),
@@ -209,7 +546,7 @@ mod tests {
 
     #[test]
     fn parse_quoted_header() {
-        let request = ParsedRequest::from_inputs(&["foo:bar baz"]).unwrap();
+        let request = from_inputs(&["foo:bar baz"]).unwrap();
 
         let headers = vec![(
             HeaderName::from_str("foo").unwrap(),
@@ -219,42 +556,195 @@ mod tests {
         assert_eq!(request.headers, HeaderMap::from_iter(headers));
     }
 
+    #[test]
+    fn parse_header_name_boundary_characters() {
+        // The range ends were once exclusive, rejecting 'Z', 'z' and '9'.
+        let request = from_inputs(&["Xyz9:bar"]).unwrap();
+
+        assert_eq!(request.headers.get("xyz9").unwrap(), "bar");
+    }
+
+    #[test]
+    fn parse_empty_header() {
+        let request = from_inputs(&["foo;"]).unwrap();
+
+        assert_eq!(request.headers.get("foo").unwrap(), "");
+        assert!(request.removed_headers.is_empty());
+    }
+
+    #[test]
+    fn parse_header_removal() {
+        let request = from_inputs(&["X-Api-Key:", "foo:=1"]).unwrap();
+
+        assert!(request.headers.is_empty());
+        assert_eq!(request.removed_headers, vec!["x-api-key"]);
+        assert_eq!(to_json(&request.body), r#"{"foo":1}"#);
+    }
+
     #[test]
     fn reject_bad_header() {
-        let error = ParsedRequest::from_inputs(&["foo bar:baz"]).unwrap_err();
-        assert_eq!(error.to_string(), "Invalid request component");
+        let error = from_inputs(&["foo bar:baz"]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid request component 'foo bar:baz' at column 4
+  foo bar:baz
+     ^ expected a letter, digit, '-' or '_' in the header name
+  hint: header names can't contain spaces; for a body value use 'name=value'"
+        );
+    }
+
+    #[test]
+    fn diagnose_invalid_components() {
+        let cases = [
+            ("value", 5, SEPARATOR),
+            ("", 0, SEPARATOR),
+            ("a[b=1", 5, CLOSING_BRACKET),
+            ("a[0", 3, CLOSING_BRACKET),
+            ("a..b=1", 2, KEY_AFTER_DOT),
+            ("a\\", 1, ESCAPED_CHARACTER),
+            ("a.b\\", 3, ESCAPED_CHARACTER),
+            ("a.b:c", 1, HEADER_NAME_CHARACTER),
+            (":x", 0, HEADER_NAME),
+            ("X-Empty;x", 8, END_OF_EMPTY_HEADER),
+        ];
+
+        for (input, offset, expected) in cases {
+            let diagnostic = parse_component(input).unwrap_err();
+
+            assert!(
+                from_inputs(&[input]).is_err(),
+                "{} should be invalid",
+                input
+            );
+            assert_eq!(diagnostic.offset, offset, "{}", input);
+            assert_eq!(
+                diagnostic.message,
+                format!("expected {}", expected),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn reject_trailing_backslash() {
+        let error = from_inputs(&[r"foo\"]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            r"Invalid request component 'foo\' at column 4
+  foo\
+     ^ expected a character to escape after '\'
+  hint: use '\\' for a literal backslash"
+        );
+    }
+
+    #[test]
+    fn reject_invalid_json_values() {
+        let error = from_inputs(&["key:=notjson"]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid request component 'key:=notjson' at column 7
+  key:=notjson
+        ^ invalid JSON: expected ident
+  hint: use '=' instead of ':=' to send it as a string"
+        );
+
+        let error = from_inputs(&["ids:=[1,2"]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid request component 'ids:=[1,2' at column 10
+  ids:=[1,2
+           ^ invalid JSON: EOF while parsing a list"
+        );
+    }
+
+    #[test]
+    fn leave_json_with_undefined_variables_to_renderer() {
+        let variables = BTreeMap::new();
+        let mut renderer = Renderer::new(&variables);
+
+        assert!(ParsedRequest::from_inputs(&["ids:={{ids}}"], &mut renderer).is_ok());
+        assert!(renderer.finish().is_err());
+    }
+
+    #[test]
+    fn keep_literal_braces_without_environment() {
+        let mut renderer = Renderer::without_variables();
+        let request = ParsedRequest::from_inputs(&["msg=Hello {{name}}"], &mut renderer).unwrap();
+
+        assert!(renderer.finish().is_ok());
+        assert_eq!(to_json(&request.body), r#"{"msg":"Hello {{name}}"}"#);
     }
 
     #[test]
     fn parse_simple_query_param() {
-        let request = ParsedRequest::from_inputs(&["foo==bar"]).unwrap();
+        let request = from_inputs(&["foo==bar"]).unwrap();
         assert_eq!(request.query, vec![("foo".to_string(), "bar".to_string())]);
     }
 
     #[test]
     fn parse_quoted_query_param() {
-        let request = ParsedRequest::from_inputs(&["foo bar==baz qux"]).unwrap();
+        let request = from_inputs(&["foo bar==baz qux"]).unwrap();
         assert_eq!(
             request.query,
             vec![("foo bar".to_string(), "baz qux".to_string())]
         );
     }
 
+    #[test]
+    fn parse_escaped_query_param_key() {
+        let request = from_inputs(&[r"a\=b==c", r"filter\:name==x", r"back\\slash==y"]).unwrap();
+        assert_eq!(
+            request.query,
+            vec![
+                ("a=b".to_string(), "c".to_string()),
+                ("filter:name".to_string(), "x".to_string()),
+                (r"back\slash".to_string(), "y".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_escaped_header_name() {
+        let request = from_inputs(&[r"X\.Trace:1", r"X\:Y:2"]);
+        assert!(request.is_err(), "':' is not valid in a header name");
+
+        let request = from_inputs(&[r"X\.Trace:1"]).unwrap();
+        assert_eq!(request.headers.get("x.trace").unwrap(), "1");
+    }
+
+    #[test]
+    fn parse_escaped_body_keys() {
+        let request = from_inputs(&[
+            r"a\.b=1",
+            r"c\[0\]=2",
+            r"d\=e=3",
+            r"f\:g:=4",
+            r"h[i\]j]=5",
+            r"k.l\.m=6",
+        ])
+        .unwrap();
+        assert_eq!(
+            to_json(&request.body),
+            r#"{"a.b":"1","c[0]":"2","d=e":"3","f:g":4,"h":{"i]j":"5"},"k":{"l.m":"6"}}"#
+        )
+    }
+
     #[test]
     fn parse_simple_body_param() {
-        let request = ParsedRequest::from_inputs(&["foo=bar"]).unwrap();
+        let request = from_inputs(&["foo=bar"]).unwrap();
         assert_eq!(to_json(&request.body), r#"{"foo":"bar"}"#)
     }
 
     #[test]
     fn parse_nested_body_param() {
-        let request = ParsedRequest::from_inputs(&["foo[bar]=baz"]).unwrap();
+        let request = from_inputs(&["foo[bar]=baz"]).unwrap();
         assert_eq!(to_json(&request.body), r#"{"foo":{"bar":"baz"}}"#)
     }
 
     #[test]
     fn parse_flexible_object_key_body_param() {
-        let request = ParsedRequest::from_inputs(&["foo[bar]baz.qux=quux"]).unwrap();
+        let request = from_inputs(&["foo[bar]baz.qux=quux"]).unwrap();
         assert_eq!(
             to_json(&request.body),
             r#"{"foo":{"bar":{"baz":{"qux":"quux"}}}}"#
@@ -263,7 +753,7 @@ mod tests {
 
     #[test]
     fn parse_flexible_array_index_body_param() {
-        let request = ParsedRequest::from_inputs(&["foo[bar]0.qux=quux"]).unwrap();
+        let request = from_inputs(&["foo[bar]0.qux=quux"]).unwrap();
         assert_eq!(
             to_json(&request.body),
             r#"{"foo":{"bar":[{"qux":"quux"}]}}"#
@@ -272,13 +762,13 @@ mod tests {
 
     #[test]
     fn parse_flexible_leading_body_param() {
-        let request = ParsedRequest::from_inputs(&["[foo][bar]=baz"]).unwrap();
+        let request = from_inputs(&["[foo][bar]=baz"]).unwrap();
         assert_eq!(to_json(&request.body), r#"{"foo":{"bar":"baz"}}"#)
     }
 
     #[test]
     fn parse_multi_nested_body_param() {
-        let request = ParsedRequest::from_inputs(&["foo[bar][baz][qux]=quux"]).unwrap();
+        let request = from_inputs(&["foo[bar][baz][qux]=quux"]).unwrap();
         assert_eq!(
             to_json(&request.body),
             r#"{"foo":{"bar":{"baz":{"qux":"quux"}}}}"#
@@ -287,31 +777,31 @@ mod tests {
 
     #[test]
     fn parse_array_end_body_param() {
-        let request = ParsedRequest::from_inputs(&["[]=foo"]).unwrap();
+        let request = from_inputs(&["[]=foo"]).unwrap();
         assert_eq!(to_json(&request.body), r#"["foo"]"#)
     }
 
     #[test]
     fn parse_nested_array_end_body_param() {
-        let request = ParsedRequest::from_inputs(&["foo[][]=bar"]).unwrap();
+        let request = from_inputs(&["foo[][]=bar"]).unwrap();
         assert_eq!(to_json(&request.body), r#"{"foo":[["bar"]]}"#)
     }
 
     #[test]
     fn parse_array_index_body_param() {
-        let request = ParsedRequest::from_inputs(&["[1]=foo"]).unwrap();
+        let request = from_inputs(&["[1]=foo"]).unwrap();
         assert_eq!(to_json(&request.body), r#"[null,"foo"]"#)
     }
 
     #[test]
     fn parse_nested_array_index_body_param() {
-        let request = ParsedRequest::from_inputs(&["foo[0][1]=bar"]).unwrap();
+        let request = from_inputs(&["foo[0][1]=bar"]).unwrap();
         assert_eq!(to_json(&request.body), r#"{"foo":[[null,"bar"]]}"#)
     }
 
     #[test]
     fn parse_mixed_body_param() {
-        let request = ParsedRequest::from_inputs(&["[][foo][bar][][1][baz]=qux"]).unwrap();
+        let request = from_inputs(&["[][foo][bar][][1][baz]=qux"]).unwrap();
         assert_eq!(
             to_json(&request.body),
             r#"[{"foo":{"bar":[[null,{"baz":"qux"}]]}}]"#
@@ -320,14 +810,8 @@ mod tests {
 
     #[test]
     fn parse_multiple_mixed_body_params() {
-        let request = ParsedRequest::from_inputs(&[
-            "a[b]=c",
-            "a[d]=e",
-            "a[f][]=g",
-            "a[f][1]=h",
-            "a[f][2][i]=j",
-        ])
-        .unwrap();
+        let request =
+            from_inputs(&["a[b]=c", "a[d]=e", "a[f][]=g", "a[f][1]=h", "a[f][2][i]=j"]).unwrap();
         assert_eq!(
             to_json(&request.body),
             r#"{"a":{"b":"c","d":"e","f":["g","h",{"i":"j"}]}}"#
@@ -336,29 +820,58 @@ mod tests {
 
     #[test]
     fn parse_raw_json_string_body_param() {
-        let request = ParsedRequest::from_inputs(&[r#"foo:="bar""#]).unwrap();
+        let request = from_inputs(&[r#"foo:="bar""#]).unwrap();
         assert_eq!(to_json(&request.body), r#"{"foo":"bar"}"#)
     }
 
     #[test]
     fn parse_raw_json_int_body_param() {
-        let request = ParsedRequest::from_inputs(&["foo:=1"]).unwrap();
+        let request = from_inputs(&["foo:=1"]).unwrap();
         assert_eq!(to_json(&request.body), r#"{"foo":1}"#)
     }
 
     #[test]
     fn parse_raw_json_null_body_param() {
-        let request = ParsedRequest::from_inputs(&["foo:=null"]).unwrap();
+        let request = from_inputs(&["foo:=null"]).unwrap();
         assert_eq!(to_json(&request.body), r#"{"foo":null}"#)
     }
 
     #[test]
     fn parse_raw_json_map_body_param() {
-        let request = ParsedRequest::from_inputs(&[r#"foo:={"bar":"baz"}"#]).unwrap();
+        let request = from_inputs(&[r#"foo:={"bar":"baz"}"#]).unwrap();
         assert_eq!(to_json(&request.body), r#"{"foo":{"bar":"baz"}}"#)
     }
 
+    #[test]
+    fn parse_typed_body_values() {
+        let request = from_inputs(&["count=5::int", "ids=1,2,3::list", "name=std::io"]).unwrap();
+        assert_eq!(
+            to_json(&request.body),
+            r#"{"count":5,"ids":["1","2","3"],"name":"std::io"}"#
+        )
+    }
+
     fn to_json(body: &[BodyValue]) -> String {
         json_builder::build(body).unwrap()
     }
+
+    #[test]
+    fn render_variables_in_values() {
+        let variables = BTreeMap::from([("id".to_string(), "42".to_string())]);
+        let mut renderer = Renderer::new(&variables);
+
+        let request = ParsedRequest::from_inputs(
+            &["X-Id:{{id}}", "id=={{id}}", "user.id:={{id}}", "{{id}}=1"],
+            &mut renderer,
+        )
+        .unwrap();
+
+        assert_eq!(request.headers.get("X-Id").unwrap(), "42");
+        assert_eq!(request.query, vec![("id".to_string(), "42".to_string())]);
+        assert_eq!(
+            json_builder::build(&request.body).unwrap(),
+            r#"{"user":{"id":42},"{{id}}":"1"}"#
+        );
+        assert!(renderer.finish().is_ok());
+    }
 }
@@ -1,39 +1,138 @@
-use std::str::FromStr;
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
 use anyhow::{bail, Context, Result};
 use http::{
-    header::{Entry, OccupiedEntry},
-    HeaderMap, HeaderName, HeaderValue, Method, Version,
+    header::{Entry, OccupiedEntry, ACCEPT, AUTHORIZATION, HOST},
+    HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Version,
 };
-use reqwest::{redirect, Response};
+use reqwest::{Client, Response};
 
 use crate::{
-    config::Config, json_builder, parser::BodyValue, session::Session, url_builder::URLBuilder,
+    auth::{password_or_prompt, Auth},
+    config::Config,
+    digest::Challenge,
+    error::Error,
+    host_pattern,
+    hsts::HstsCache,
+    json_builder,
+    parser::BodyValue,
+    secret::Secret,
+    session::Session,
+    signing::SigningProfile,
+    template::Renderer,
+    transport::Transport,
+    url_builder::{expand_alias, URLBuilder},
+    validate::Problem,
 };
 
 /// Wraps a reqwest::RequestBuilder to provide additional functionality by
 /// parsing user input and stored sessions
 pub struct RequestBuilder {
-    pub url: URLBuilder,
-    pub headers: HeaderMap,
-    pub body: Option<String>,
-    pub version: Version,
+    url: URLBuilder,
+    headers: HeaderMap,
+    body: Option<String>,
+    version: Version,
+    auth: Option<Auth>,
+    signing: Option<SigningProfile>,
+    transport: Transport,
+
+    /// Whether to record Strict-Transport-Security policies from responses
+    hsts: bool,
+
+    /// Query parameters from the session, added by `add_query` unless the
+    /// same parameter is given explicitly
+    default_query: Vec<(String, String)>,
+
+    /// Headers to leave out of the request, even if they are added when it
+    /// is sent, e.g. by signing
+    removed_headers: Vec<HeaderName>,
+
+    /// Whether `add_data` sends string body values that look like numbers or
+    /// booleans as such
+    infer_types: bool,
+
+    /// Warnings from building the request, e.g. for inferred body value types
+    warnings: Vec<Problem>,
+
+    /// Where OAuth2 tokens are cached, instead of the data home
+    token_cache: Option<PathBuf>,
+}
+
+/// How `RequestBuilder::from_input` resolves the URL
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct InputOptions {
+    /// The scheme to use, overriding any in the URL or session
+    pub scheme: Option<String>,
+
+    /// Whether to prepend the session's base path to the URL path
+    pub base_path: bool,
+
+    /// Whether hosts with a cached Strict-Transport-Security policy default
+    /// to HTTPS, and policies from responses are recorded
+    pub hsts: bool,
+}
+
+impl Default for InputOptions {
+    fn default() -> Self {
+        Self {
+            scheme: None,
+            base_path: true,
+            hsts: true,
+        }
+    }
 }
 
 impl RequestBuilder {
     /// Creates a new RequestBuilder from a URL and configuration object
-    pub async fn from_input(scheme: Option<&str>, url: &str, config: &Config) -> Result<Self> {
-        let mut url = URLBuilder::from_input(&url, &config.fallback_hostname)?;
+    ///
+    /// Aliases in the URL are expanded before the session is looked up.
+    /// The session's port fills in a missing port, and its base path is
+    /// prepended to the URL path unless disabled in the options. Variables,
+    /// if an environment is selected, are rendered in the session's header
+    /// values. Warnings about the session store are available from
+    /// `take_warnings`.
+    pub async fn from_input(
+        url: &str,
+        config: &Config,
+        variables: Option<&BTreeMap<String, String>>,
+        options: &InputOptions,
+    ) -> Result<Self, Error> {
+        let url = expand_alias(url, &config.aliases);
+        let mut url =
+            URLBuilder::from_input(&url, &config.fallback_hostname).map_err(Error::Usage)?;
         let authority = url.authority().context("URL has authority")?;
-        let session = Session::load(&authority).await?.unwrap_or_default();
+        let mut warnings = vec![];
+        let session = Session::load_with_warnings(&authority, &mut warnings)
+            .await?
+            .unwrap_or_default();
 
-        if url.scheme == None {
+        url.apply_session(&session, options.base_path);
+
+        if url.scheme.is_none() {
             let hostname = url.hostname.as_ref().context("hostname parsed")?;
-            url.scheme = Some(get_scheme(hostname, &session, &config.http_hostnames))
+            let hsts_cache = match options.hsts {
+                // A corrupt cache shouldn't stop every request, so it's
+                // treated as empty until a response replaces it.
+                true => Some(HstsCache::load().await.unwrap_or_else(|err| {
+                    warnings.push(Problem::Warning(format!(
+                        "Could not load the Strict-Transport-Security cache: {:#}",
+                        err
+                    )));
+                    HstsCache::default()
+                })),
+                false => None,
+            };
+
+            url.scheme = Some(get_scheme(hostname, &session, config, hsts_cache.as_ref()))
         }
 
-        if let Some(scheme) = scheme {
-            url.scheme = Some(scheme.to_string());
+        if let Some(scheme) = &options.scheme {
+            url.scheme = Some(scheme.clone());
         }
 
         let mut header_map = HeaderMap::new();
@@ -42,34 +141,177 @@ impl RequestBuilder {
             host = format!("{}:{}", host, port);
         }
 
-        let host_header = HeaderValue::from_str(&host)?;
+        let host_header = HeaderValue::from_str(&host).context("valid Host header")?;
         header_map.append("Host", host_header);
 
         if let Some(headers) = session.headers.as_ref() {
-            for (key, values) in headers {
-                for value in values {
-                    add_header(&mut header_map, key, value)?;
-                }
-            }
+            add_session_headers(&mut header_map, headers, variables).await?;
+        }
+
+        // Userinfo in the URL takes precedence over session credentials.
+        let auth = match &url.username {
+            Some(username) => Some(Auth::Basic {
+                username: username.clone(),
+                password: url.password.as_deref().map(Secret::from),
+            }),
+            None => session.auth.clone(),
+        };
+
+        let signing = match &session.signing_profile {
+            Some(profile) => Some(
+                profile
+                    .resolve(&config.signing_profiles)
+                    .map_err(Error::Config)?,
+            ),
+            None => None,
+        };
+
+        let transport = session
+            .transport
+            .clone()
+            .unwrap_or_default()
+            .or(config.transport.clone());
+
+        let default_query = session
+            .query
+            .iter()
+            .flatten()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+
+        // Credentials take precedence over an Authorization session header.
+        if auth.is_some() {
+            header_map.remove(AUTHORIZATION);
         }
 
         Ok(Self {
             url,
             headers: header_map,
             body: None,
-            version: Version::default(),
+            version: transport.version(),
+            signing,
+            transport,
+            hsts: options.hsts,
+            default_query,
+            removed_headers: vec![],
+            infer_types: false,
+            warnings,
+            auth,
+            token_cache: None,
         })
     }
 
-    /// Adds the given query parameters to the request
+    /// Returns the URL the request is sent to
+    pub fn url(&self) -> &URLBuilder {
+        &self.url
+    }
+
+    /// Returns the headers sent with the request, apart from any added when
+    /// it is sent, e.g. by signing
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Returns the request body
+    pub fn body(&self) -> Option<&str> {
+        self.body.as_deref()
+    }
+
+    /// Returns the HTTP version to send the request with
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    /// Returns the headers left out of the request
+    pub fn removed_headers(&self) -> &[HeaderName] {
+        &self.removed_headers
+    }
+
+    /// Returns whether the request has credentials or an Authorization
+    /// header, or leaves the header out
+    pub(crate) fn has_authorization(&self) -> bool {
+        self.auth.is_some()
+            || self.headers.contains_key(AUTHORIZATION)
+            || self.removed_headers.contains(&AUTHORIZATION)
+    }
+
+    /// Authenticates the request with the given credentials, replacing any
+    /// previously set credentials
+    ///
+    /// The credentials aren't resolved until `authorize` is called, so
+    /// replaced credentials are never prompted for.
+    pub(crate) fn auth(mut self, auth: Auth) -> Self {
+        self.headers.remove(AUTHORIZATION);
+        self.auth = Some(auth);
+        self
+    }
+
+    /// Resolves the credentials into an Authorization header, prompting for a
+    /// missing password
+    ///
+    /// This is called by `send`, so it's only needed to see the header before
+    /// the request is sent.
+    pub(crate) async fn authorize(&mut self) -> Result<(), Error> {
+        if self.headers.contains_key(AUTHORIZATION) {
+            return Ok(());
+        }
+
+        if let Some(value) = match &self.auth {
+            Some(auth) => header_value(auth, self.token_cache.as_deref()).await?,
+            None => None,
+        } {
+            self.headers.insert(AUTHORIZATION, value);
+        }
+
+        Ok(())
+    }
+
+    /// Signs the request with the given HMAC signing profile
+    pub(crate) fn signing(mut self, profile: SigningProfile) -> Self {
+        self.signing = Some(profile);
+        self
+    }
+
+    /// Returns the warnings found since the last call
+    pub fn take_warnings(&mut self) -> Vec<Problem> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Sends string body values that look like numbers or booleans as such,
+    /// with a warning for each
+    pub fn infer_types(mut self, infer_types: bool) -> Self {
+        self.infer_types = infer_types;
+        self
+    }
+
+    /// Applies transport settings, which take precedence over those from the
+    /// session and config file
+    pub(crate) fn transport(mut self, transport: Transport) -> Self {
+        self.transport = transport.or(self.transport);
+        self.version = self.transport.version();
+        self
+    }
+
+    /// Adds the given query parameters to the request, along with any session
+    /// query parameters not given explicitly
     pub fn add_query(mut self, query: &[(String, String)]) -> Self {
-        if query.is_empty() {
+        let existing: Vec<String> =
+            url::form_urlencoded::parse(self.url.query.as_deref().unwrap_or_default().as_bytes())
+                .map(|(key, _)| key.into_owned())
+                .collect();
+
+        let defaults: Vec<(String, String)> = std::mem::take(&mut self.default_query)
+            .into_iter()
+            .filter(|(key, _)| !existing.contains(key) && !query.iter().any(|(k, _)| k == key))
+            .collect();
+
+        if query.is_empty() && defaults.is_empty() {
             return self;
         }
 
         let mut serializer = url::form_urlencoded::Serializer::new(String::new());
 
-        for (key, value) in query {
+        for (key, value) in defaults.iter().chain(query) {
             serializer.append_pair(key, value);
         }
 
@@ -78,20 +320,50 @@ impl RequestBuilder {
             _ => Some(serializer.finish()),
         };
 
-        return self;
+        self
     }
 
     /// Merges the given headers into the request
+    ///
+    /// An explicit Authorization header replaces any configured credentials.
     pub fn merge_headers(mut self, headers: HeaderMap) -> Result<Self> {
         if headers.is_empty() {
             return Ok(self);
         }
 
+        if headers.contains_key(AUTHORIZATION) {
+            self.auth = None;
+        }
+
         replace_headers(&mut self.headers, headers);
 
         Ok(self)
     }
 
+    /// Removes headers from the request, including those from the session
+    ///
+    /// Removing the Authorization header removes any configured credentials.
+    /// Host and Accept can't be removed, as the HTTP client always sends them.
+    pub fn remove_headers(mut self, names: &[HeaderName]) -> Result<Self> {
+        for name in names {
+            if name == HOST || name == ACCEPT {
+                bail!(
+                    "{} can't be removed, as the HTTP client always sends it",
+                    name
+                );
+            }
+
+            self.headers.remove(name);
+
+            if name == AUTHORIZATION {
+                self.auth = None;
+            }
+        }
+
+        self.removed_headers.extend_from_slice(names);
+        Ok(self)
+    }
+
     /// Adds data to the request body
     pub fn add_data(mut self, values: &[BodyValue], data: Option<&str>) -> Result<Self> {
         if data.is_some() && !values.is_empty() {
@@ -102,56 +374,265 @@ impl RequestBuilder {
             self.body = Some(data.to_owned());
         }
 
-        if !values.is_empty() {
+        if !values.is_empty() && self.infer_types {
+            let (body, mut warnings) = json_builder::build_inferring_types(values)?;
+            self.body = Some(body);
+            self.warnings.append(&mut warnings);
+        } else if !values.is_empty() {
             self.body = Some(json_builder::build(values)?);
         }
 
         Ok(self)
     }
 
-    /// Sets the HTTP version of the request
-    pub fn version(mut self, version: Version) -> Self {
-        self.version = version;
-        self
+    /// Sends the request
+    ///
+    /// With digest credentials, the request is first sent without an
+    /// Authorization header and then retried in response to the server's
+    /// challenge, unless the challenge can't be answered, which is a warning.
+    /// With OAuth2 credentials, a rejected request is retried once
+    /// with a freshly fetched token. With AWS credentials, the request is
+    /// signed before it is sent, followed by any HMAC signing profile. The
+    /// final response's Strict-Transport-Security policy is recorded, with a
+    /// warning if that fails.
+    pub async fn send(&mut self, method: Method) -> Result<Response, Error> {
+        let client = self
+            .transport
+            .client_builder()
+            .await?
+            .build()
+            .context("build HTTP client")?;
+
+        self.authorize().await?;
+
+        let body = self.body.as_deref().unwrap_or_default().as_bytes();
+
+        if let Some(Auth::AwsSigV4(aws)) = &self.auth {
+            let url = self.url.build()?;
+            aws.sign(method.as_str(), &url, &mut self.headers, body)
+                .await?;
+        }
+
+        if let Some(profile) = &self.signing {
+            profile
+                .sign(method.as_str(), &self.url, &mut self.headers, body)
+                .await?;
+        }
+
+        let response = self.execute(&client, &method).await?;
+
+        let response = match self.auth.clone() {
+            Some(Auth::Digest { username, password }) => {
+                self.answer_digest_challenge(&client, &method, response, &username, password)
+                    .await?
+            }
+
+            Some(auth @ Auth::OAuth2(_)) if response.status() == StatusCode::UNAUTHORIZED => {
+                if let Auth::OAuth2(oauth2) = &auth {
+                    oauth2
+                        .invalidate(self.token_cache.as_deref())
+                        .await
+                        .map_err(Error::Config)?;
+                }
+
+                if let Some(value) = header_value(&auth, self.token_cache.as_deref()).await? {
+                    self.headers.insert(AUTHORIZATION, value);
+                }
+
+                self.execute(&client, &method).await?
+            }
+
+            _ => response,
+        };
+
+        // The response has arrived, so failing to record its policy is only
+        // worth a warning.
+        if self.hsts {
+            if let Err(err) = HstsCache::record_response(&response).await {
+                self.warnings.push(Problem::Warning(format!(
+                    "Could not record the Strict-Transport-Security policy: {:#}",
+                    err
+                )));
+            }
+        }
+
+        Ok(response)
     }
 
-    /// Sends the request
-    pub async fn send(&mut self, method: Method, max_redirects: usize) -> Result<Response> {
-        let client = reqwest::ClientBuilder::new()
-            .redirect(redirect::Policy::limited(max_redirects))
-            .build()?;
+    async fn answer_digest_challenge(
+        &mut self,
+        client: &Client,
+        method: &Method,
+        mut response: Response,
+        username: &str,
+        password: Option<Secret>,
+    ) -> Result<Response, Error> {
+        let mut resolved_password = None;
+
+        // A second challenge is only answered if the server reports that the
+        // nonce we used was stale.
+        for attempt in 0..2 {
+            if response.status() != StatusCode::UNAUTHORIZED {
+                break;
+            }
+
+            // A challenge that can't be answered leaves the 401 to be shown.
+            let challenge = match Challenge::from_headers(response.headers()) {
+                Ok(Some(challenge)) => challenge,
+                Ok(None) => break,
+                Err(err) => {
+                    self.warnings.push(Problem::Warning(format!(
+                        "Could not answer the digest challenge: {:#}",
+                        err
+                    )));
+                    break;
+                }
+            };
+
+            if attempt > 0 && !challenge.stale {
+                break;
+            }
+
+            let password = match resolved_password.take() {
+                Some(password) => password,
+                None => password_or_prompt(username, password.as_ref()).await?,
+            };
+
+            let value = challenge.authorize(
+                username,
+                &password,
+                method.as_str(),
+                &self.url.request_target(),
+            )?;
+
+            resolved_password = Some(password);
+
+            self.headers.insert(AUTHORIZATION, value);
+            response = self.execute(client, method).await?;
+        }
 
+        Ok(response)
+    }
+
+    /// Sends the request, categorising any error by what went wrong
+    async fn execute(&self, client: &Client, method: &Method) -> Result<Response, Error> {
+        let url = self.url.build()?;
+
+        self.request(client, method)?
+            .send()
+            .await
+            .map_err(|err| Error::from_reqwest(&url, err))
+    }
+
+    fn request(&self, client: &Client, method: &Method) -> Result<reqwest::RequestBuilder> {
         let mut request = client
-            .request(method, self.url.build()?)
+            .request(method.clone(), self.url.build()?)
             .version(self.version);
-        request = request.headers(self.headers.clone());
 
-        if let Some(body) = self.body.take() {
-            request = request.body(body);
+        let mut headers = self.headers.clone();
+        for name in &self.removed_headers {
+            headers.remove(name);
         }
+        request = request.headers(headers);
 
-        let response = request.send().await?;
+        if let Some(body) = &self.body {
+            request = request.body(body.clone());
+        }
 
-        Ok(response)
+        Ok(request)
     }
 }
 
-fn get_scheme(hostname: &str, session: &Session, http_hostnames: &[String]) -> String {
+/// Returns the scheme to use for a hostname when none is given
+///
+/// The session's scheme takes precedence, then any HSTS policy, then
+/// `https_hostnames`, then `http_hostnames`. Private and loopback IP addresses
+/// default to HTTP, and anything else to HTTPS.
+fn get_scheme(
+    hostname: &str,
+    session: &Session,
+    config: &Config,
+    hsts: Option<&HstsCache>,
+) -> String {
     if let Some(scheme) = &session.scheme {
         return scheme.as_str().to_string();
     }
 
-    if http_hostnames.contains(&hostname.to_string()) {
-        return "http".to_string();
+    if hsts.is_some_and(|hsts| hsts.is_secure(hostname))
+        || host_pattern::matches_any(&config.https_hostnames, hostname)
+    {
+        "https".to_string()
+    } else if host_pattern::matches_any(&config.http_hostnames, hostname)
+        || host_pattern::is_private_ip(hostname)
+    {
+        "http".to_string()
     } else {
-        return "https".to_string();
+        "https".to_string()
     }
 }
 
-fn add_header(map: &mut HeaderMap, key: &str, value: &str) -> Result<()> {
+/// Adds a header, resolving its value if it is a secret reference
+///
+/// Resolved secrets are marked as sensitive so that they are not printed.
+/// Variables are only rendered in plain values.
+/// Adds the session's headers, rendering variables in their values
+///
+/// Invalid headers are session store errors.
+async fn add_session_headers(
+    map: &mut HeaderMap,
+    headers: &HashMap<String, Vec<Secret>>,
+    variables: Option<&BTreeMap<String, String>>,
+) -> Result<(), Error> {
+    let mut renderer = match variables {
+        Some(variables) => Renderer::new(variables),
+        None => Renderer::without_variables(),
+    };
+
+    for (key, values) in headers {
+        for value in values {
+            add_header(map, key, value, &mut renderer)
+                .await
+                .with_context(|| format!("session header {}", key))
+                .map_err(Error::Session)?;
+        }
+    }
+
+    renderer
+        .finish()
+        .context("render session headers")
+        .map_err(Error::Session)
+}
+
+/// Returns the Authorization header value for the credentials
+///
+/// Failing to fetch an OAuth2 token, e.g. because the token endpoint rejected
+/// the client, is a config error.
+async fn header_value(
+    auth: &Auth,
+    token_cache: Option<&Path>,
+) -> Result<Option<HeaderValue>, Error> {
+    let value = auth.header_value(token_cache).await;
+
+    match auth {
+        Auth::OAuth2(_) => value.map_err(Error::Config),
+        _ => Ok(value?),
+    }
+}
+
+async fn add_header(
+    map: &mut HeaderMap,
+    key: &str,
+    value: &Secret,
+    renderer: &mut Renderer<'_>,
+) -> Result<()> {
     let key = HeaderName::from_str(key).context("valid header name")?;
-    let value = HeaderValue::from_str(value).context("valid header value")?;
-    map.append(key, value);
+    let resolved = match value {
+        Secret::Plain(value) => renderer.render(value),
+        secret => secret.resolve().await?,
+    };
+    let mut header_value = HeaderValue::from_str(&resolved).context("valid header value")?;
+    header_value.set_sensitive(value.is_reference());
+    map.append(key, header_value);
     Ok(())
 }
 
@@ -184,3 +665,335 @@ fn replace_headers(dst: &mut HeaderMap, src: HeaderMap) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        fs,
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+        task::JoinHandle,
+    };
+
+    use std::env;
+
+    use super::*;
+    use crate::oauth2::OAuth2;
+
+    /// Serves the canned responses in turn, one per connection, and returns
+    /// the raw requests received
+    async fn mock_server(responses: Vec<String>) -> (String, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+
+        let handle = tokio::spawn(async move {
+            let mut requests = vec![];
+
+            for response in responses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = vec![0; 4096];
+                let n = stream.read(&mut request).await.unwrap();
+
+                stream.write_all(response.as_bytes()).await.unwrap();
+                requests.push(String::from_utf8_lossy(&request[..n]).to_string());
+            }
+
+            requests
+        });
+
+        (url, handle)
+    }
+
+    fn response(status: &str, headers: &[&str]) -> String {
+        let mut response = format!("HTTP/1.1 {}\r\n", status);
+
+        for header in headers {
+            response.push_str(header);
+            response.push_str("\r\n");
+        }
+
+        response + "content-length: 0\r\nconnection: close\r\n\r\n"
+    }
+
+    fn json_response(body: &str) -> String {
+        format!(
+            "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )
+    }
+
+    fn digest_challenge(nonce: &str, stale: bool) -> String {
+        response(
+            "401 Unauthorized",
+            &[&format!(
+                r#"www-authenticate: Digest realm="test", qop="auth", nonce="{}", opaque="xyz", stale={}"#,
+                nonce, stale
+            )],
+        )
+    }
+
+    fn digest_auth() -> Auth {
+        Auth::Digest {
+            username: "user".to_string(),
+            password: Some("pass".into()),
+        }
+    }
+
+    fn builder(url: &str) -> RequestBuilder {
+        RequestBuilder {
+            url: URLBuilder::from_input(url, "localhost").unwrap(),
+            headers: HeaderMap::new(),
+            body: None,
+            version: Version::HTTP_11,
+            auth: None,
+            signing: None,
+            transport: Transport::default(),
+            hsts: false,
+            default_query: vec![],
+            removed_headers: vec![],
+            infer_types: false,
+            warnings: vec![],
+            token_cache: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn mark_secret_headers_sensitive() {
+        let variables = BTreeMap::new();
+        let mut renderer = Renderer::new(&variables);
+        let mut headers = HeaderMap::new();
+
+        let secret = Secret::Command {
+            command: vec!["echo".to_string(), "token".to_string()],
+        };
+        add_header(&mut headers, "X-Token", &secret, &mut renderer)
+            .await
+            .unwrap();
+        add_header(&mut headers, "X-Plain", &"value".into(), &mut renderer)
+            .await
+            .unwrap();
+
+        // Sensitive values are redacted in verbose output without
+        // --show-secrets.
+        assert_eq!(headers["x-token"], "token");
+        assert!(headers["x-token"].is_sensitive());
+        assert!(!headers["x-plain"].is_sensitive());
+    }
+
+    #[tokio::test]
+    async fn skip_resolving_replaced_credentials() {
+        let mut req = builder("http://localhost/")
+            .auth(Auth::Basic {
+                username: "session".to_string(),
+                password: Some(Secret::Env {
+                    env: "GET_TEST_UNSET_PASSWORD".to_string(),
+                }),
+            })
+            .auth(Auth::Bearer {
+                token: "abc".into(),
+            });
+
+        req.authorize().await.unwrap();
+
+        assert_eq!(req.headers[AUTHORIZATION], "Bearer abc");
+    }
+
+    #[tokio::test]
+    async fn answer_digest_challenge() {
+        let (url, handle) = mock_server(vec![
+            digest_challenge("abc", false),
+            response("200 OK", &[]),
+        ])
+        .await;
+
+        let mut req = builder(&url).auth(digest_auth());
+        let response = req.send(Method::GET).await.unwrap();
+        let requests = handle.await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(requests.len(), 2);
+        assert!(!requests[0].contains("authorization:"));
+        assert!(requests[1]
+            .contains(r#"authorization: Digest username="user", realm="test", nonce="abc""#));
+    }
+
+    #[tokio::test]
+    async fn retry_stale_digest_nonce() {
+        let (url, handle) = mock_server(vec![
+            digest_challenge("abc", false),
+            digest_challenge("def", true),
+            response("200 OK", &[]),
+        ])
+        .await;
+
+        let mut req = builder(&url).auth(digest_auth());
+        let response = req.send(Method::GET).await.unwrap();
+        let requests = handle.await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(requests.len(), 3);
+        assert!(requests[2].contains(r#"nonce="def""#));
+    }
+
+    #[tokio::test]
+    async fn warn_about_malformed_digest_challenge() {
+        let (url, handle) = mock_server(vec![response(
+            "401 Unauthorized",
+            &[r#"www-authenticate: Digest realm="test", qop="auth-int", nonce="abc""#],
+        )])
+        .await;
+
+        let mut req = builder(&url).auth(digest_auth());
+        let response = req.send(Method::GET).await.unwrap();
+        let warnings = req.take_warnings();
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(handle.await.unwrap().len(), 1);
+        assert!(matches!(
+            &warnings[..],
+            [Problem::Warning(warning)] if warning.contains("Unsupported digest qop")
+        ));
+    }
+
+    #[tokio::test]
+    async fn give_up_on_rejected_digest_credentials() {
+        let (url, handle) = mock_server(vec![
+            digest_challenge("abc", false),
+            digest_challenge("def", false),
+        ])
+        .await;
+
+        let mut req = builder(&url).auth(digest_auth());
+        let response = req.send(Method::GET).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(handle.await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn refresh_oauth2_token_after_rejection() {
+        let (token_url, token_handle) = mock_server(vec![
+            json_response(r#"{"access_token":"a1","refresh_token":"r2"}"#),
+            json_response(r#"{"access_token":"a2"}"#),
+        ])
+        .await;
+        let (url, handle) = mock_server(vec![
+            response("401 Unauthorized", &[]),
+            response("200 OK", &[]),
+        ])
+        .await;
+
+        let token_cache = env::temp_dir().join(format!("get-oauth2-{}.json", uuid::Uuid::new_v4()));
+        let mut req = builder(&url).auth(Auth::OAuth2(OAuth2 {
+            token_url,
+            client_id: "client".to_string(),
+            client_secret: None,
+            scopes: vec![],
+            refresh_token: Some("r1".into()),
+        }));
+        req.token_cache = Some(token_cache.clone());
+        let response = req.send(Method::GET).await.unwrap();
+        let _ = fs::remove_file(&token_cache).await;
+        let token_requests = token_handle.await.unwrap();
+        let requests = handle.await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(token_requests[0].contains("grant_type=refresh_token&refresh_token=r1"));
+        // The rotated refresh token survives the rejected access token.
+        assert!(token_requests[1].contains("grant_type=refresh_token&refresh_token=r2"));
+        assert!(requests[0].contains("authorization: Bearer a1"));
+        assert!(requests[1].contains("authorization: Bearer a2"));
+    }
+
+    #[test]
+    fn reject_removing_host() {
+        let error = builder("http://localhost/")
+            .remove_headers(&[HOST])
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            error.to_string(),
+            "host can't be removed, as the HTTP client always sends it"
+        );
+    }
+
+    #[tokio::test]
+    async fn report_token_endpoint_rejection_as_config_error() {
+        let (token_url, handle) = mock_server(vec![response("401 Unauthorized", &[])]).await;
+        let token_cache = env::temp_dir().join(format!("get-oauth2-{}.json", uuid::Uuid::new_v4()));
+
+        let mut req = builder("http://localhost/").auth(Auth::OAuth2(OAuth2 {
+            token_url,
+            client_id: "client".to_string(),
+            client_secret: Some("wrong".into()),
+            scopes: vec![],
+            refresh_token: None,
+        }));
+        req.token_cache = Some(token_cache);
+        let error = req.send(Method::GET).await.unwrap_err();
+        handle.await.unwrap();
+
+        assert_eq!(error.exit_code(), 3);
+    }
+
+    #[tokio::test]
+    async fn report_invalid_session_header_as_session_error() {
+        let headers = HashMap::from([("bad name".to_string(), vec!["value".into()])]);
+        let error = add_session_headers(&mut HeaderMap::new(), &headers, None)
+            .await
+            .unwrap_err();
+
+        assert_eq!(error.exit_code(), 4);
+        assert_eq!(error.to_string(), "session header bad name");
+    }
+
+    #[tokio::test]
+    async fn remove_session_and_signing_headers() {
+        let (url, handle) = mock_server(vec![response("200 OK", &[])]).await;
+
+        let mut req = builder(&url);
+        // As added from the session
+        req.headers
+            .insert("x-session", HeaderValue::from_static("1"));
+        let profile: SigningProfile = serde_json::from_value(serde_json::json!({
+            "parts": ["method", "timestamp"],
+            "key": "secret",
+            "timestamp_header": "X-Timestamp",
+            "signature_header": "X-Signature",
+        }))
+        .unwrap();
+
+        let mut req = req
+            .signing(profile)
+            .remove_headers(&[
+                HeaderName::from_static("x-session"),
+                HeaderName::from_static("x-timestamp"),
+            ])
+            .unwrap();
+        req.send(Method::GET).await.unwrap();
+        let request = handle.await.unwrap().remove(0);
+
+        assert!(!request.contains("x-session"));
+        assert!(!request.contains("x-timestamp"));
+        assert!(request.contains("x-signature:"));
+    }
+
+    #[test]
+    fn reject_removing_client_headers() {
+        for name in [HOST, ACCEPT] {
+            let err = builder("http://localhost/")
+                .remove_headers(std::slice::from_ref(&name))
+                .err()
+                .unwrap();
+            assert_eq!(
+                err.to_string(),
+                format!(
+                    "{} can't be removed, as the HTTP client always sends it",
+                    name
+                )
+            );
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use std::{
+    collections::HashMap,
+    env,
+    path::PathBuf,
+    sync::{Mutex, OnceLock},
+};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio::{fs, process::Command};
+
+/// A value that is either stored literally or resolved at request time
+///
+/// References let session files point at credentials instead of storing them
+/// in plaintext, e.g. `{"env": "GITHUB_TOKEN"}`, `{"file": "~/.secrets/token"}`
+/// or `{"command": ["pass", "show", "api"]}`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+#[non_exhaustive]
+pub enum Secret {
+    Plain(String),
+    Env { env: String },
+    File { file: String },
+    Command { command: Vec<String> },
+}
+
+impl Secret {
+    /// Returns whether the value is a reference rather than a literal
+    pub fn is_reference(&self) -> bool {
+        !matches!(self, Self::Plain(_))
+    }
+
+    /// Resolves the value
+    ///
+    /// Trailing newlines are trimmed from file contents and command output.
+    /// Command output is cached for the lifetime of the process, so a command
+    /// is only run once per invocation.
+    pub async fn resolve(&self) -> Result<String> {
+        match self {
+            Self::Plain(value) => Ok(value.clone()),
+
+            Self::Env { env } => {
+                env::var(env).with_context(|| format!("read environment variable {}", env))
+            }
+
+            Self::File { file } => {
+                let contents = fs::read_to_string(expand_home(file)?)
+                    .await
+                    .with_context(|| format!("read secret file {}", file))?;
+
+                Ok(trim_newline(&contents))
+            }
+
+            Self::Command { command } => {
+                if let Some(output) = cached_output(command) {
+                    return Ok(output);
+                }
+
+                let Some((program, args)) = command.split_first() else {
+                    bail!("Secret command must not be empty");
+                };
+
+                let output = Command::new(program)
+                    .args(args)
+                    .output()
+                    .await
+                    .with_context(|| format!("run secret command {}", program))?;
+
+                if !output.status.success() {
+                    bail!("Secret command {} exited with {}", program, output.status);
+                }
+
+                let output = trim_newline(&String::from_utf8(output.stdout)?);
+                cache_output(command, &output);
+
+                Ok(output)
+            }
+        }
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(value: &str) -> Self {
+        Self::Plain(value.to_string())
+    }
+}
+
+fn command_cache() -> &'static Mutex<HashMap<Vec<String>, String>> {
+    static CACHE: OnceLock<Mutex<HashMap<Vec<String>, String>>> = OnceLock::new();
+    CACHE.get_or_init(Default::default)
+}
+
+fn cached_output(command: &[String]) -> Option<String> {
+    let cache = command_cache()
+        .lock()
+        .unwrap_or_else(|err| err.into_inner());
+    cache.get(command).cloned()
+}
+
+fn cache_output(command: &[String], output: &str) {
+    let mut cache = command_cache()
+        .lock()
+        .unwrap_or_else(|err| err.into_inner());
+    cache.insert(command.to_vec(), output.to_string());
+}
+
+fn trim_newline(value: &str) -> String {
+    value.trim_end_matches(['\r', '\n']).to_string()
+}
+
+/// Expands a leading `~/` to the user's home directory
+pub fn expand_home(path: &str) -> Result<PathBuf> {
+    match path.strip_prefix("~/") {
+        Some(rest) => Ok(homedir::my_home()?.context("home dir")?.join(rest)),
+        None => Ok(PathBuf::from(path)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+
+    use super::*;
+
+    fn command(args: &[&str]) -> Secret {
+        Secret::Command {
+            command: args.iter().map(|arg| arg.to_string()).collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn resolve_plain_value() {
+        let secret = Secret::from("token");
+
+        assert!(!secret.is_reference());
+        assert_eq!(secret.resolve().await.unwrap(), "token");
+    }
+
+    #[tokio::test]
+    async fn resolve_env() {
+        let secret = Secret::Env {
+            env: "PATH".to_string(),
+        };
+
+        assert!(secret.is_reference());
+        assert_eq!(secret.resolve().await.unwrap(), env::var("PATH").unwrap());
+
+        let missing = Secret::Env {
+            env: "GET_TEST_SECRET_UNSET".to_string(),
+        };
+        assert_eq!(
+            missing.resolve().await.unwrap_err().to_string(),
+            "read environment variable GET_TEST_SECRET_UNSET"
+        );
+    }
+
+    #[tokio::test]
+    async fn resolve_file() {
+        let path = temp_dir().join(format!("get-secret-{}", uuid::Uuid::new_v4()));
+        fs::write(&path, "token\r\n").await.unwrap();
+
+        let secret = Secret::File {
+            file: path.to_string_lossy().to_string(),
+        };
+        let resolved = secret.resolve().await;
+        fs::remove_file(&path).await.unwrap();
+
+        assert_eq!(resolved.unwrap(), "token");
+        assert!(Secret::File {
+            file: path.to_string_lossy().to_string()
+        }
+        .resolve()
+        .await
+        .unwrap_err()
+        .to_string()
+        .starts_with("read secret file"));
+    }
+
+    #[tokio::test]
+    async fn resolve_command_once() {
+        let path = temp_dir().join(format!("get-secret-{}", uuid::Uuid::new_v4()));
+        let script = format!("echo run >> {0}; wc -l < {0}", path.display());
+        let secret = command(&["sh", "-c", &script]);
+
+        let first = secret.resolve().await.unwrap();
+        let second = secret.resolve().await.unwrap();
+        let runs = fs::read_to_string(&path).await.unwrap();
+        fs::remove_file(&path).await.unwrap();
+
+        assert_eq!(first.trim(), "1");
+        assert_eq!(second, first);
+        assert_eq!(runs, "run\n");
+    }
+
+    #[tokio::test]
+    async fn reject_failing_command() {
+        assert_eq!(
+            command(&["sh", "-c", "exit 3"])
+                .resolve()
+                .await
+                .unwrap_err()
+                .to_string(),
+            "Secret command sh exited with exit status: 3"
+        );
+        assert_eq!(
+            command(&[]).resolve().await.unwrap_err().to_string(),
+            "Secret command must not be empty"
+        );
+    }
+
+    #[test]
+    fn parse_references() {
+        let secrets: Vec<Secret> = serde_json::from_str(
+            r#"["token", {"env": "TOKEN"}, {"file": "~/token"}, {"command": ["pass", "api"]}]"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            secrets,
+            vec![
+                Secret::from("token"),
+                Secret::Env {
+                    env: "TOKEN".to_string()
+                },
+                Secret::File {
+                    file: "~/token".to_string()
+                },
+                command(&["pass", "api"]),
+            ]
+        );
+    }
+}
@@ -1,40 +1,85 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     env,
     io::ErrorKind,
     path::{Path, PathBuf},
 };
-use tokio::{fs::File, io::AsyncReadExt};
+use tokio::{fs, io::AsyncWriteExt};
 
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-#[derive(Clone, Serialize, Deserialize)]
+use crate::{
+    auth::Auth,
+    encryption::{self, Key},
+    error::Error,
+    format::{self, Format},
+    secret::Secret,
+    signing::SigningProfileRef,
+    transport::Transport,
+    validate::{self, Problem},
+};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Scheme {
-    HTTP,
-    HTTPS,
+    Http,
+    Https,
 }
 
 impl Scheme {
     pub fn as_str(&self) -> &str {
         match self {
-            Self::HTTP => "http",
-            Self::HTTPS => "https",
+            Self::Http => "http",
+            Self::Https => "https",
         }
     }
 }
 
 /// A saved set of configuration for making requests against a given authority
-#[derive(Clone, Serialize, Deserialize)]
+///
+/// Sessions are stored by `get session` and applied by
+/// `RequestBuilder::from_input`. They serialize in the session store's
+/// format.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Session {
     /// The headers to include in the request
     ///
     /// A header can have more than one value, so we use a `Vec` to store them.
-    pub headers: Option<HashMap<String, Vec<String>>>,
+    /// Values may be secret references, which are resolved at request time.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) headers: Option<HashMap<String, Vec<Secret>>>,
 
     /// The scheme to use when making requests
-    pub scheme: Option<Scheme>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) scheme: Option<Scheme>,
+
+    /// The port to use when the URL does not specify one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) port: Option<u16>,
+
+    /// A path prefix for request paths, e.g. "/api/v1"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) base_path: Option<String>,
+
+    /// The credentials to authenticate requests with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) auth: Option<Auth>,
+
+    /// The HMAC signing profile to sign requests with, either the name of a
+    /// profile in the config file or an inline profile
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) signing_profile: Option<SigningProfileRef>,
+
+    /// Transport settings, which take precedence over those in the config
+    /// file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) transport: Option<Transport>,
+
+    /// Query parameters to include in the request unless given explicitly
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) query: Option<BTreeMap<String, String>>,
 }
 
 impl Default for Session {
@@ -50,46 +95,506 @@ impl Session {
         Self {
             headers: None,
             scheme: None,
+            port: None,
+            base_path: None,
+            auth: None,
+            signing_profile: None,
+            transport: None,
+            query: None,
         }
     }
 
     /// Loads a session for the given authority
-    pub async fn load(authority: &str) -> Result<Option<Self>> {
-        let store = SessionStore::load().await?;
+    ///
+    /// Problems with the session store that don't prevent loading it, e.g.
+    /// unknown keys, are ignored.
+    pub async fn load(authority: &str) -> Result<Option<Self>, Error> {
+        Self::load_with_warnings(authority, &mut vec![]).await
+    }
+
+    /// Loads a session for the given authority from the session store at the
+    /// given path, rather than the one in the data home
+    ///
+    /// As with `load`, problems that don't prevent loading it are ignored.
+    pub async fn load_from(path: &Path, authority: &str) -> Result<Option<Self>, Error> {
+        let (store, _) = SessionStore::read(path).await.map_err(Error::Session)?;
         Ok(store.get(authority).cloned())
     }
+
+    /// The scheme to use when the URL has none, `http` or `https`
+    pub fn scheme(&self) -> Option<&str> {
+        self.scheme.as_ref().map(Scheme::as_str)
+    }
+
+    /// The port to use when the URL has none
+    pub fn port(&self) -> Option<u16> {
+        self.port
+    }
+
+    /// The path prefix for request paths
+    pub fn base_path(&self) -> Option<&str> {
+        self.base_path.as_deref()
+    }
+
+    /// The names of the headers to include in the request
+    ///
+    /// Their values aren't exposed, as they may be secrets.
+    pub fn header_names(&self) -> impl Iterator<Item = &str> {
+        self.headers
+            .iter()
+            .flat_map(|headers| headers.keys().map(String::as_str))
+    }
+
+    /// Query parameters to include in the request unless given explicitly
+    pub fn query(&self) -> Option<&BTreeMap<String, String>> {
+        self.query.as_ref()
+    }
+
+    /// Loads a session for the given authority, adding any problems with the
+    /// session store that don't prevent loading it to `warnings`
+    pub(crate) async fn load_with_warnings(
+        authority: &str,
+        warnings: &mut Vec<Problem>,
+    ) -> Result<Option<Self>, Error> {
+        let store = SessionStore::load(warnings).await?;
+        Ok(store.get(authority).cloned())
+    }
+
+    /// Replaces secrets with references to environment variables, so that the
+    /// session can be shared, returning a description of each change
+    ///
+    /// Credentials and the values of headers with secret-looking names are
+    /// replaced, unless they are already references. Query parameters with
+    /// secret-looking names can't be references, so they are removed.
+    pub(crate) fn redact(&mut self, authority: &str) -> Vec<String> {
+        let mut changes = vec![];
+
+        let mut replace = |secret: &mut Secret, field: &str| {
+            if let Secret::Plain(_) = secret {
+                let env = env_name(authority, field);
+                changes.push(format!("{}: {} replaced by ${}", authority, field, env));
+                *secret = Secret::Env { env };
+            }
+        };
+
+        for (name, values) in self.headers.iter_mut().flatten() {
+            if is_secret_name(name) {
+                for value in values {
+                    replace(value, name);
+                }
+            }
+        }
+
+        match &mut self.auth {
+            Some(Auth::Basic { password, .. } | Auth::Digest { password, .. }) => {
+                if let Some(password) = password {
+                    replace(password, "password");
+                }
+            }
+            Some(Auth::Bearer { token }) => replace(token, "token"),
+            Some(Auth::OAuth2(oauth2)) => {
+                if let Some(client_secret) = &mut oauth2.client_secret {
+                    replace(client_secret, "client_secret");
+                }
+                if let Some(refresh_token) = &mut oauth2.refresh_token {
+                    replace(refresh_token, "refresh_token");
+                }
+            }
+            Some(Auth::AwsSigV4(_)) | None => {}
+        }
+
+        if let Some(SigningProfileRef::Inline(profile)) = &mut self.signing_profile {
+            replace(&mut profile.key, "signing_key");
+        }
+
+        if let Some(query) = &mut self.query {
+            query.retain(|name, _| {
+                let secret = is_secret_name(name);
+                if secret {
+                    changes.push(format!("{}: query parameter {} removed", authority, name));
+                }
+                !secret
+            });
+
+            if query.is_empty() {
+                self.query = None;
+            }
+        }
+
+        changes
+    }
+}
+
+/// How to handle sessions that already exist when importing
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Import {
+    /// Only add sessions for new authorities, failing on any conflict
+    New,
+
+    /// Add settings that aren't set yet, keeping existing ones on conflict
+    Merge,
+
+    /// Replace existing sessions with the imported ones
+    Replace,
 }
 
 /// A map of URL authorities to their respective session configurations
-#[derive(Serialize, Deserialize)]
-struct SessionStore(HashMap<String, Session>);
+#[derive(Default, Serialize, Deserialize)]
+pub struct SessionStore(BTreeMap<String, Session>);
 
 impl SessionStore {
     fn get(&self, authority: &str) -> Option<&Session> {
         self.0.get(authority)
     }
 
-    async fn load() -> Result<SessionStore> {
-        let sessions_path = get_data_home()?.join("get").join("sessions.json");
+    /// Returns the path of the session store, which may be in any supported
+    /// format, defaulting to `sessions.json`
+    pub async fn path(warnings: &mut Vec<Problem>) -> Result<PathBuf> {
+        let dir = get_data_home()?.join("get");
+
+        match format::find(&dir, "sessions", warnings).await? {
+            Some(path) => Ok(path),
+            None => Ok(dir.join("sessions.json")),
+        }
+    }
+
+    /// Loads the session store, adding warnings for unknown keys to
+    /// `warnings`
+    async fn load(warnings: &mut Vec<Problem>) -> Result<SessionStore, Error> {
+        Self::read_store(warnings).await.map_err(Error::Session)
+    }
+
+    async fn read_store(warnings: &mut Vec<Problem>) -> Result<SessionStore> {
+        let path = Self::path(warnings).await?;
+
+        let (session_store, problems) = Self::read(&path).await?;
+        warnings.extend(problems);
 
-        match File::open(sessions_path).await {
-            Ok(mut file) => {
-                let mut dest = Vec::new();
-                file.read_to_end(&mut dest).await?;
+        Ok(session_store)
+    }
 
-                let session_store: SessionStore =
-                    serde_json::from_slice(&dest).context("parse session store")?;
+    /// Reads the session store, decrypting it if it is encrypted
+    async fn read(path: &Path) -> Result<(SessionStore, Vec<Problem>)> {
+        match fs::read(path).await {
+            Ok(contents) if encryption::is_encrypted(&contents) => {
+                let key = Key::for_decryption(&contents).await?;
+                let contents = encryption::decrypt(&contents, &key)
+                    .with_context(|| format!("decrypt {}", path.display()))?;
 
-                Ok(session_store)
+                Format::from_path(path).parse(&contents, path)
             }
 
-            Err(err) if err.kind() == ErrorKind::NotFound => Ok(SessionStore(HashMap::new())),
+            Ok(contents) => Format::from_path(path).parse(&contents, path),
+
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok((SessionStore::default(), vec![])),
             Err(err) => Err(err).context("open session store"),
         }
     }
+
+    /// Validates the session store, returning its path and the problems found
+    pub async fn check() -> Result<(PathBuf, Vec<Problem>)> {
+        let mut problems = vec![];
+        let path = Self::path(&mut problems).await?;
+
+        match Self::read(&path).await {
+            Ok((session_store, warnings)) => {
+                problems.extend(warnings);
+                problems.extend(session_store.problems(&path));
+            }
+            Err(err) => problems.push(Problem::Error(err.to_string())),
+        }
+
+        Ok((path, problems))
+    }
+
+    /// Converts the session store to another format, returning the new path
+    pub async fn migrate(to: Format) -> Result<PathBuf> {
+        let path = Self::path(&mut vec![]).await?;
+
+        if encryption::is_encrypted(&fs::read(&path).await.unwrap_or_default()) {
+            bail!("{} is encrypted; decrypt it first", path.display());
+        }
+
+        format::migrate::<SessionStore>(&path, to).await
+    }
+
+    /// Encrypts the session store in place, returning its path
+    ///
+    /// The contents are encrypted as they are, so comments are kept.
+    pub async fn encrypt() -> Result<PathBuf> {
+        let path = Self::path(&mut vec![]).await?;
+        let contents = Self::read_raw(&path).await?;
+
+        if encryption::is_encrypted(&contents) {
+            bail!("{} is already encrypted", path.display());
+        }
+
+        // Refuse to encrypt a file that doesn't load, as it'd be harder to fix.
+        Format::from_path(&path).parse::<SessionStore>(&contents, &path)?;
+
+        let key = Key::for_encryption().await?;
+        write_private(&path, &encryption::encrypt(&contents, &key)?).await?;
+
+        Ok(path)
+    }
+
+    /// Decrypts the session store in place, returning its path
+    pub async fn decrypt() -> Result<PathBuf> {
+        let path = Self::path(&mut vec![]).await?;
+        let contents = Self::read_raw(&path).await?;
+
+        if !encryption::is_encrypted(&contents) {
+            bail!("{} is not encrypted", path.display());
+        }
+
+        let key = Key::for_decryption(&contents).await?;
+        write_private(&path, &encryption::decrypt(&contents, &key)?).await?;
+
+        Ok(path)
+    }
+
+    /// Exports the sessions for the given authorities as a store of their
+    /// own, returning it with a description of each redaction
+    ///
+    /// Problems with the session store that don't prevent loading it are
+    /// added to `warnings`.
+    pub async fn export(
+        authorities: &[String],
+        redact: bool,
+        warnings: &mut Vec<Problem>,
+    ) -> Result<(SessionStore, Vec<String>)> {
+        let store = Self::load(warnings).await?;
+        let mut exported = SessionStore::default();
+        let mut changes = vec![];
+
+        for authority in authorities {
+            let mut session = store
+                .get(authority)
+                .cloned()
+                .with_context(|| format!("No session for {}", authority))?;
+
+            if redact {
+                changes.extend(session.redact(authority));
+            }
+
+            exported.0.insert(authority.clone(), session);
+        }
+
+        Ok((exported, changes))
+    }
+
+    /// Imports sessions from a file, returning a description of each conflict
+    ///
+    /// Nothing is written if importing only new sessions and there are
+    /// conflicts. The store is rewritten in its own format, so comments are
+    /// not kept, and it is encrypted again if it was encrypted. Problems with
+    /// the file that don't prevent importing it are added to `warnings`.
+    pub async fn import(
+        file: &Path,
+        mode: Import,
+        warnings: &mut Vec<Problem>,
+    ) -> Result<Vec<String>> {
+        let contents = fs::read(file)
+            .await
+            .with_context(|| format!("read {}", file.display()))?;
+        let (imported, problems) =
+            Format::from_path(file).parse::<SessionStore>(&contents, file)?;
+        warnings.extend(problems);
+
+        let path = Self::path(&mut vec![]).await?;
+        let (contents, key) = match fs::read(&path).await {
+            Ok(contents) if encryption::is_encrypted(&contents) => {
+                let key = Key::for_decryption(&contents).await?;
+                (encryption::decrypt(&contents, &key)?, Some(key))
+            }
+            Ok(contents) => (contents, None),
+            Err(err) if err.kind() == ErrorKind::NotFound => (b"{}".to_vec(), None),
+            Err(err) => return Err(err).context("open session store"),
+        };
+        let (mut store, _) = Format::from_path(&path).parse::<SessionStore>(&contents, &path)?;
+
+        let conflicts = store.merge(imported, mode)?;
+        if mode == Import::New && !conflicts.is_empty() {
+            return Ok(conflicts);
+        }
+
+        let mut contents = Format::from_path(&path).serialize(&store)?.into_bytes();
+        if let Some(key) = &key {
+            contents = encryption::encrypt(&contents, key)?;
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        write_private(&path, &contents).await?;
+
+        Ok(conflicts)
+    }
+
+    /// Merges imported sessions into the store, returning a description of
+    /// each conflict
+    fn merge(&mut self, imported: SessionStore, mode: Import) -> Result<Vec<String>> {
+        let mut conflicts = vec![];
+
+        for (authority, session) in imported.0 {
+            let Some(existing) = self.0.get_mut(&authority) else {
+                self.0.insert(authority, session);
+                continue;
+            };
+
+            if *existing == session {
+                continue;
+            }
+
+            match mode {
+                Import::New => conflicts.push(format!("{}: session already exists", authority)),
+
+                Import::Replace => {
+                    conflicts.push(format!("{}: replaced existing session", authority));
+                    *existing = session;
+                }
+
+                Import::Merge => {
+                    let mut merged = serde_json::to_value(&*existing)?;
+                    let incoming = serde_json::to_value(&session)?;
+
+                    for key in merge_values(&mut merged, incoming, &["headers", "query"]) {
+                        conflicts.push(format!("{}: kept existing {}", authority, key));
+                    }
+
+                    *existing = serde_json::from_value(merged)?;
+                }
+            }
+        }
+
+        Ok(conflicts)
+    }
+
+    async fn read_raw(path: &Path) -> Result<Vec<u8>> {
+        match fs::read(path).await {
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                bail!("No session store at {}", path.display())
+            }
+            result => result.context("open session store"),
+        }
+    }
+
+    /// Checks values that are valid JSON but can't be sent
+    fn problems(&self, path: &Path) -> Vec<Problem> {
+        let mut problems = vec![];
+        for authority in self.0.keys() {
+            let mut headers: Vec<_> = self.0[authority].headers.iter().flatten().collect();
+            headers.sort_by_key(|(name, _)| *name);
+
+            for (name, values) in headers {
+                let location = format!("{}: {}.headers.{}", path.display(), authority, name);
+                problems.extend(validate::check_header_name(name, &location));
+
+                for value in values {
+                    if let Secret::Plain(value) = value {
+                        problems.extend(validate::check_header_value(value, &location));
+                    }
+                }
+            }
+        }
+
+        problems
+    }
+}
+
+/// Adds the keys of one object that the other doesn't have, returning the
+/// keys that both have with different values
+///
+/// The maps with the given keys are merged by entry rather than as a whole.
+fn merge_values(base: &mut Value, incoming: Value, maps: &[&str]) -> Vec<String> {
+    let (Value::Object(base), Value::Object(incoming)) = (base, incoming) else {
+        return vec![];
+    };
+
+    let mut conflicts = vec![];
+
+    for (key, value) in incoming {
+        match base.get_mut(&key) {
+            None => {
+                base.insert(key, value);
+            }
+            Some(existing) if *existing == value => {}
+            Some(existing) if maps.contains(&key.as_str()) => {
+                for entry in merge_values(existing, value, &[]) {
+                    conflicts.push(format!("{}.{}", key, entry));
+                }
+            }
+            Some(_) => conflicts.push(key),
+        }
+    }
+
+    conflicts
+}
+
+/// Returns whether a header or query parameter name suggests a secret value
+fn is_secret_name(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+
+    [
+        "auth", "cookie", "key", "password", "secret", "session", "token",
+    ]
+    .iter()
+    .any(|word| name.contains(word))
+}
+
+/// Returns the name of an environment variable for a secret, e.g.
+/// `API_EXAMPLE_COM_AUTHORIZATION`
+fn env_name(authority: &str, field: &str) -> String {
+    format!("{}_{}", authority, field)
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' => c.to_ascii_uppercase(),
+            _ => '_',
+        })
+        .collect()
 }
 
-fn get_data_home() -> Result<PathBuf> {
+/// Replaces a file, readable only by the user, without leaving it partly
+/// written if interrupted
+///
+/// The contents are written to a temporary file that is created with
+/// owner-only permissions, so they're never readable by others.
+pub async fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    let temp = PathBuf::from(temp);
+
+    // A file left by an interrupted write would stop it being created anew.
+    match fs::remove_file(&temp).await {
+        Err(err) if err.kind() != ErrorKind::NotFound => {
+            return Err(err).with_context(|| format!("remove {}", temp.display()));
+        }
+        _ => {}
+    }
+
+    let result = async {
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+
+        let mut file = options.open(&temp).await?;
+        file.write_all(contents).await?;
+        file.sync_all().await?;
+
+        fs::rename(&temp, path).await
+    }
+    .await;
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp).await;
+    }
+
+    result.with_context(|| format!("write {}", path.display()))
+}
+
+/// Returns the base directory for user data, e.g. the session store
+pub fn get_data_home() -> Result<PathBuf> {
     match env::var("XDG_DATA_HOME") {
         Ok(path) => Ok(Path::new(&path).to_path_buf()),
         Err(_) => Ok(homedir::my_home()?
@@ -98,3 +603,137 @@ fn get_data_home() -> Result<PathBuf> {
             .join("share")),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(json: &str) -> SessionStore {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn redact_secrets() {
+        let mut session: Session = serde_json::from_str(
+            r#"{
+                "headers": {"X-Api-Key": ["abc"], "X-Team": ["core"], "Cookie": [{"env": "C"}]},
+                "auth": {"type": "basic", "username": "u", "password": "p"},
+                "query": {"access_token": "t", "page": "1"}
+            }"#,
+        )
+        .unwrap();
+
+        let changes = session.redact("api.example.com:8443");
+
+        let headers = session.headers.as_ref().unwrap();
+        assert_eq!(
+            headers["X-Api-Key"],
+            vec![Secret::Env {
+                env: "API_EXAMPLE_COM_8443_X_API_KEY".to_string()
+            }]
+        );
+        assert_eq!(headers["X-Team"], vec![Secret::Plain("core".to_string())]);
+        assert_eq!(
+            headers["Cookie"],
+            vec![Secret::Env {
+                env: "C".to_string()
+            }]
+        );
+
+        assert!(matches!(
+            session.auth,
+            Some(Auth::Basic {
+                password: Some(Secret::Env { .. }),
+                ..
+            })
+        ));
+        assert_eq!(
+            session.query,
+            Some(BTreeMap::from([("page".to_string(), "1".to_string())]))
+        );
+        assert_eq!(changes.len(), 3);
+    }
+
+    #[test]
+    fn import_sessions() {
+        let existing = r#"{
+            "a.test": {"port": 8080, "headers": {"X-Team": ["core"]}},
+            "b.test": {"port": 80}
+        }"#;
+        let imported = r#"{
+            "a.test": {"port": 9090, "base_path": "/v1", "headers": {"X-Team": ["web"], "X-Env": ["dev"]}},
+            "b.test": {"port": 80},
+            "c.test": {"port": 443}
+        }"#;
+
+        let mut new = store(existing);
+        let conflicts = new.merge(store(imported), Import::New).unwrap();
+        assert_eq!(conflicts, vec!["a.test: session already exists"]);
+
+        let mut merged = store(existing);
+        let conflicts = merged.merge(store(imported), Import::Merge).unwrap();
+        assert_eq!(
+            conflicts,
+            vec![
+                "a.test: kept existing headers.X-Team",
+                "a.test: kept existing port"
+            ]
+        );
+
+        let a = &merged.0["a.test"];
+        assert_eq!(a.port, Some(8080));
+        assert_eq!(a.base_path.as_deref(), Some("/v1"));
+        assert_eq!(a.headers.as_ref().unwrap().len(), 2);
+        assert!(merged.0.contains_key("c.test"));
+
+        let mut replaced = store(existing);
+        let conflicts = replaced.merge(store(imported), Import::Replace).unwrap();
+        assert_eq!(conflicts, vec!["a.test: replaced existing session"]);
+        assert_eq!(replaced.0["a.test"].port, Some(9090));
+    }
+
+    #[tokio::test]
+    async fn write_private_file() {
+        let path = env::temp_dir().join(format!("get-private-{}", uuid::Uuid::new_v4()));
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".tmp");
+
+        // A leftover temporary file, e.g. from an interrupted write
+        fs::write(&temp, "stale").await.unwrap();
+        write_private(&path, b"{}").await.unwrap();
+
+        assert_eq!(fs::read_to_string(&path).await.unwrap(), "{}");
+        assert!(fs::metadata(&temp).await.is_err());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).await.unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        fs::remove_file(&path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn load_session_from_path() {
+        let path = env::temp_dir().join(format!("get-sessions-{}.json", uuid::Uuid::new_v4()));
+        fs::write(
+            &path,
+            r#"{"api.example.com": {"port": 8443, "base_path": "/v1", "headers": {"X-Team": ["a"]}}}"#,
+        )
+        .await
+        .unwrap();
+
+        let session = Session::load_from(&path, "api.example.com").await;
+        let missing = Session::load_from(&path, "example.com").await;
+        fs::remove_file(&path).await.unwrap();
+
+        let session = session.unwrap().unwrap();
+        assert_eq!(session.port(), Some(8443));
+        assert_eq!(session.base_path(), Some("/v1"));
+        assert_eq!(session.scheme(), None);
+        assert_eq!(session.header_names().collect::<Vec<_>>(), vec!["X-Team"]);
+        assert!(missing.unwrap().is_none());
+    }
+}
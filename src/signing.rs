@@ -0,0 +1,321 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use http::{HeaderMap, HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::{secret::Secret, url_builder::URLBuilder};
+
+/// A declarative HMAC request signing scheme
+///
+/// The configured request parts are joined with the separator into a
+/// canonical string, which is signed with the key and placed in the signature
+/// header, e.g.:
+///
+/// ```json
+/// {
+///   "parts": ["method", "path", "timestamp", "body_digest"],
+///   "key": {"env": "API_SIGNING_KEY"},
+///   "timestamp_header": "X-Timestamp",
+///   "signature_header": "X-Signature"
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SigningProfile {
+    /// The request parts to canonicalize, in order
+    pub parts: Vec<SigningPart>,
+
+    /// The separator placed between parts
+    #[serde(default = "default_separator")]
+    pub separator: String,
+
+    /// The hash algorithm used for the HMAC and the body digest
+    #[serde(default)]
+    pub algorithm: HashAlgorithm,
+
+    /// The encoding of the signature and body digest
+    #[serde(default)]
+    pub encoding: Encoding,
+
+    /// The signing key
+    pub key: Secret,
+
+    /// The format of the timestamp
+    #[serde(default)]
+    pub timestamp_format: TimestampFormat,
+
+    /// A header to send the timestamp in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp_header: Option<String>,
+
+    /// A header to send the body digest in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body_digest_header: Option<String>,
+
+    /// The header to send the signature in
+    pub signature_header: String,
+
+    /// A template for the signature header value, where "{signature}" is
+    /// replaced with the signature
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature_format: Option<String>,
+}
+
+/// A part of the request included in the canonical string
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SigningPart {
+    Method,
+    Host,
+    Path,
+    Query,
+    Timestamp,
+    BodyDigest,
+    Header(String),
+    Literal(String),
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    Sha512,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    #[default]
+    Hex,
+    Base64,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimestampFormat {
+    #[default]
+    Unix,
+    UnixMillis,
+    Rfc3339,
+}
+
+/// A signing profile given by name or inline in a session
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SigningProfileRef {
+    Named(String),
+    Inline(Box<SigningProfile>),
+}
+
+impl SigningProfileRef {
+    /// Returns the referenced profile, looking up named profiles in the given
+    /// set of configured profiles
+    pub fn resolve(&self, profiles: &HashMap<String, SigningProfile>) -> Result<SigningProfile> {
+        match self {
+            Self::Named(name) => profiles
+                .get(name)
+                .cloned()
+                .with_context(|| format!("Signing profile {} is not configured", name)),
+            Self::Inline(profile) => Ok(*profile.clone()),
+        }
+    }
+}
+
+fn default_separator() -> String {
+    "\n".to_string()
+}
+
+impl SigningProfile {
+    /// Signs a request, adding the output headers
+    pub async fn sign(
+        &self,
+        method: &str,
+        url: &URLBuilder,
+        headers: &mut HeaderMap,
+        body: &[u8],
+    ) -> Result<()> {
+        let key = self.key.resolve().await?;
+        self.sign_at(Utc::now(), &key, method, url, headers, body)
+    }
+
+    fn sign_at(
+        &self,
+        time: DateTime<Utc>,
+        key: &str,
+        method: &str,
+        url: &URLBuilder,
+        headers: &mut HeaderMap,
+        body: &[u8],
+    ) -> Result<()> {
+        let timestamp = match self.timestamp_format {
+            TimestampFormat::Unix => time.timestamp().to_string(),
+            TimestampFormat::UnixMillis => time.timestamp_millis().to_string(),
+            TimestampFormat::Rfc3339 => time.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        };
+
+        let body_digest = self.encode(&self.digest(body));
+
+        if let Some(name) = &self.timestamp_header {
+            insert_header(headers, name, &timestamp)?;
+        }
+
+        if let Some(name) = &self.body_digest_header {
+            insert_header(headers, name, &body_digest)?;
+        }
+
+        let mut parts = vec![];
+
+        for part in &self.parts {
+            parts.push(match part {
+                SigningPart::Method => method.to_string(),
+                SigningPart::Host => url.authority()?,
+                SigningPart::Path => url.path.clone().unwrap_or(String::from("/")),
+                SigningPart::Query => url.query.clone().unwrap_or_default(),
+                SigningPart::Timestamp => timestamp.clone(),
+                SigningPart::BodyDigest => body_digest.clone(),
+                SigningPart::Header(name) => {
+                    let values = headers
+                        .get_all(name.as_str())
+                        .iter()
+                        .map(|value| value.to_str())
+                        .collect::<Result<Vec<_>, _>>()?;
+                    values.join(",")
+                }
+                SigningPart::Literal(value) => value.clone(),
+            });
+        }
+
+        let signature = self.encode(&self.hmac(key.as_bytes(), &parts.join(&self.separator)));
+
+        let value = match &self.signature_format {
+            Some(format) => format.replace("{signature}", &signature),
+            None => signature,
+        };
+
+        insert_header(headers, &self.signature_header, &value)?;
+
+        Ok(())
+    }
+
+    fn digest(&self, data: &[u8]) -> Vec<u8> {
+        match self.algorithm {
+            HashAlgorithm::Sha256 => Sha256::digest(data).to_vec(),
+            HashAlgorithm::Sha512 => Sha512::digest(data).to_vec(),
+        }
+    }
+
+    fn hmac(&self, key: &[u8], data: &str) -> Vec<u8> {
+        match self.algorithm {
+            HashAlgorithm::Sha256 => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("any key length");
+                mac.update(data.as_bytes());
+                mac.finalize().into_bytes().to_vec()
+            }
+            HashAlgorithm::Sha512 => {
+                let mut mac = Hmac::<Sha512>::new_from_slice(key).expect("any key length");
+                mac.update(data.as_bytes());
+                mac.finalize().into_bytes().to_vec()
+            }
+        }
+    }
+
+    fn encode(&self, data: &[u8]) -> String {
+        match self.encoding {
+            Encoding::Hex => hex::encode(data),
+            Encoding::Base64 => STANDARD.encode(data),
+        }
+    }
+}
+
+fn insert_header(headers: &mut HeaderMap, name: &str, value: &str) -> Result<()> {
+    let name = HeaderName::from_bytes(name.as_bytes()).context("valid header name")?;
+    let value = HeaderValue::from_str(value).context("valid header value")?;
+    headers.insert(name, value);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn profile() -> SigningProfile {
+        serde_json::from_str(
+            r#"{
+                "parts": ["method", "path", "query", "timestamp", "body_digest", {"header": "x-key-id"}],
+                "key": "secret",
+                "timestamp_header": "X-Timestamp",
+                "body_digest_header": "X-Content-Digest",
+                "signature_header": "X-Signature"
+            }"#,
+        )
+        .unwrap()
+    }
+
+    fn sign(profile: &SigningProfile) -> HeaderMap {
+        let url = URLBuilder::from_input("example.com/foo?bar=baz", "localhost").unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert("x-key-id", HeaderValue::from_static("key-1"));
+
+        profile
+            .sign_at(
+                Utc.timestamp_opt(1700000000, 0).unwrap(),
+                "secret",
+                "POST",
+                &url,
+                &mut headers,
+                b"{}",
+            )
+            .unwrap();
+
+        headers
+    }
+
+    #[test]
+    fn sign_canonical_string() {
+        let headers = sign(&profile());
+
+        assert_eq!(headers["x-timestamp"], "1700000000");
+        assert_eq!(
+            headers["x-content-digest"],
+            "44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a"
+        );
+        assert_eq!(
+            headers["x-signature"],
+            "13047d5f34f32be108d416644fd70b2de3b342eb6944e8876b3961d490fb3e9b"
+        );
+    }
+
+    #[test]
+    fn format_signature_header() {
+        let mut profile = profile();
+        profile.encoding = Encoding::Base64;
+        profile.signature_format = Some("HMAC-SHA256 key-1:{signature}".to_string());
+
+        let headers = sign(&profile);
+        let value = headers["x-signature"].to_str().unwrap();
+
+        assert!(value.starts_with("HMAC-SHA256 key-1:"));
+        assert_eq!(
+            headers["x-content-digest"],
+            "RBNvo1WzZ4oRRq0W9+hknpT7T8If536DEMBg9hyq/4o="
+        );
+    }
+
+    #[test]
+    fn resolve_named_profile() {
+        let profiles = HashMap::from([("api".to_string(), profile())]);
+
+        let named = SigningProfileRef::Named("api".to_string());
+        assert_eq!(named.resolve(&profiles).unwrap(), profile());
+
+        let missing = SigningProfileRef::Named("other".to_string());
+        assert!(missing.resolve(&profiles).is_err());
+    }
+}
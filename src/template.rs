@@ -0,0 +1,268 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{bail, Result};
+use chrono::{SecondsFormat, Utc};
+use rand::Rng;
+use uuid::Uuid;
+
+/// The most hex digits `{{random_hex N}}` generates
+const MAX_RANDOM_HEX: usize = 1024;
+
+/// Replaces `{{name}}` placeholders with the values of variables or of
+/// generators
+///
+/// Generators produce a fresh value for each placeholder rendered:
+///
+/// - `{{uuid}}`, a random UUID
+/// - `{{now_rfc3339}}`, the current time, e.g. `2024-01-02T03:04:05Z`
+/// - `{{now_unix}}`, the current Unix timestamp in seconds
+/// - `{{random_hex N}}`, N random hex digits
+///
+/// Variables take precedence over generators of the same name. Undefined
+/// variables and invalid generator arguments are collected across every
+/// input rendered, so that they can all be reported at once by `finish`.
+/// Without variables, e.g. when no environment is selected, other
+/// placeholders are left as they are rather than reported.
+pub struct Renderer<'a> {
+    variables: Option<&'a BTreeMap<String, String>>,
+    undefined: BTreeSet<String>,
+    invalid: BTreeSet<String>,
+}
+
+impl<'a> Renderer<'a> {
+    /// Creates a renderer for the given variables
+    pub fn new(variables: &'a BTreeMap<String, String>) -> Self {
+        Self {
+            variables: Some(variables),
+            undefined: BTreeSet::new(),
+            invalid: BTreeSet::new(),
+        }
+    }
+
+    /// Creates a renderer that only fills in generators
+    pub fn without_variables() -> Self {
+        Self {
+            variables: None,
+            undefined: BTreeSet::new(),
+            invalid: BTreeSet::new(),
+        }
+    }
+
+    /// Renders the placeholders in the input
+    ///
+    /// Placeholders of undefined variables are left as they are. Braces that
+    /// don't enclose a variable name are not treated as placeholders.
+    pub fn render(&mut self, input: &str) -> String {
+        let mut output = String::with_capacity(input.len());
+        let mut rest = input;
+
+        while let Some(start) = rest.find("{{") {
+            output.push_str(&rest[..start]);
+            rest = &rest[start..];
+
+            let Some(end) = rest.find("}}") else {
+                break;
+            };
+
+            let mut words = rest[2..end].split_whitespace();
+            let name = words.next().unwrap_or_default();
+            let args: Vec<&str> = words.collect();
+
+            if !is_variable_name(name) {
+                output.push_str("{{");
+                rest = &rest[2..];
+                continue;
+            }
+
+            let variable = match args[..] {
+                [] => self.variables.and_then(|variables| variables.get(name)),
+                _ => None,
+            };
+
+            match (variable, generate(name, &args)) {
+                (Some(value), _) => output.push_str(value),
+                (None, Some(Ok(value))) => output.push_str(&value),
+                (None, Some(Err(message))) => {
+                    self.invalid
+                        .insert(format!("{}: {}", &rest[..end + 2], message));
+                    output.push_str(&rest[..end + 2]);
+                }
+                (None, None) => {
+                    if self.variables.is_some() {
+                        self.undefined.insert(name.to_string());
+                    }
+                    output.push_str(&rest[..end + 2]);
+                }
+            }
+
+            rest = &rest[end + 2..];
+        }
+
+        output.push_str(rest);
+        output
+    }
+
+    /// Whether any input rendered so far referenced an undefined variable or
+    /// gave a generator invalid arguments
+    pub fn has_errors(&self) -> bool {
+        !self.undefined.is_empty() || !self.invalid.is_empty()
+    }
+
+    /// Fails if any input referenced an undefined variable or gave a
+    /// generator invalid arguments
+    pub fn finish(self) -> Result<()> {
+        let mut errors = vec![];
+
+        if !self.undefined.is_empty() {
+            let names: Vec<String> = self.undefined.into_iter().collect();
+            errors.push(format!("Undefined variables: {}", names.join(", ")));
+        }
+
+        errors.extend(self.invalid);
+
+        if !errors.is_empty() {
+            bail!("{}", errors.join("\n"));
+        }
+
+        Ok(())
+    }
+}
+
+/// Generates the value of a generator placeholder, or returns `None` if
+/// there's no generator with the name
+fn generate(name: &str, args: &[&str]) -> Option<Result<String, String>> {
+    let value = match (name, args) {
+        ("uuid", []) => Ok(Uuid::new_v4().to_string()),
+        ("now_rfc3339", []) => Ok(Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)),
+        ("now_unix", []) => Ok(Utc::now().timestamp().to_string()),
+
+        ("random_hex", [digits]) => match digits.parse::<usize>() {
+            Ok(digits @ 1..=MAX_RANDOM_HEX) => {
+                let mut rng = rand::thread_rng();
+                Ok((0..digits)
+                    .map(|_| char::from_digit(rng.gen_range(0..16), 16).unwrap_or('0'))
+                    .collect())
+            }
+            _ => Err(format!(
+                "expected a number of digits from 1 to {}",
+                MAX_RANDOM_HEX
+            )),
+        },
+        ("random_hex", _) => Err("expected a number of digits".to_string()),
+
+        ("uuid" | "now_rfc3339" | "now_unix", _) => Err("expected no arguments".to_string()),
+        _ => return None,
+    };
+
+    Some(value)
+}
+
+fn is_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variables() -> BTreeMap<String, String> {
+        BTreeMap::from([
+            ("host".to_string(), "staging.example.com".to_string()),
+            ("token".to_string(), "abc123".to_string()),
+        ])
+    }
+
+    #[test]
+    fn render_variables() {
+        let variables = variables();
+        let mut renderer = Renderer::new(&variables);
+
+        assert_eq!(
+            renderer.render("{{host}}/users"),
+            "staging.example.com/users"
+        );
+        assert_eq!(
+            renderer.render("Authorization:Bearer {{ token }}"),
+            "Authorization:Bearer abc123"
+        );
+        assert!(renderer.finish().is_ok());
+    }
+
+    #[test]
+    fn ignore_braces_without_variable_names() {
+        let variables = variables();
+        let mut renderer = Renderer::new(&variables);
+
+        assert_eq!(renderer.render(r#"{{"a": 1}}"#), r#"{{"a": 1}}"#);
+        assert_eq!(renderer.render("{{host"), "{{host");
+        assert!(renderer.finish().is_ok());
+    }
+
+    #[test]
+    fn render_generators() {
+        let variables = variables();
+        let mut renderer = Renderer::new(&variables);
+
+        let uuid = renderer.render("{{uuid}}");
+        assert!(Uuid::parse_str(&uuid).is_ok());
+        assert_ne!(renderer.render("{{uuid}}"), uuid);
+
+        let hex = renderer.render("{{ random_hex 16 }}");
+        assert_eq!(hex.len(), 16);
+        assert!(hex.chars().all(|c| c.is_ascii_hexdigit()));
+
+        assert!(renderer.render("{{now_unix}}").parse::<i64>().is_ok());
+        assert!(renderer.render("{{now_rfc3339}}").ends_with('Z'));
+        assert!(renderer.finish().is_ok());
+    }
+
+    #[test]
+    fn prefer_variables_to_generators() {
+        let variables = BTreeMap::from([("uuid".to_string(), "fixed".to_string())]);
+        let mut renderer = Renderer::new(&variables);
+
+        assert_eq!(renderer.render("{{uuid}}"), "fixed");
+    }
+
+    #[test]
+    fn report_invalid_generator_arguments() {
+        let variables = variables();
+        let mut renderer = Renderer::new(&variables);
+
+        assert_eq!(renderer.render("{{random_hex x}}"), "{{random_hex x}}");
+        renderer.render("{{uuid 4}}");
+
+        assert_eq!(
+            renderer.finish().unwrap_err().to_string(),
+            "{{random_hex x}}: expected a number of digits from 1 to 1024\n{{uuid 4}}: expected no arguments"
+        );
+    }
+
+    #[test]
+    fn keep_placeholders_without_variables() {
+        let mut renderer = Renderer::without_variables();
+
+        assert_eq!(renderer.render("Hello {{name}}"), "Hello {{name}}");
+        assert!(Uuid::parse_str(&renderer.render("{{uuid}}")).is_ok());
+        assert!(renderer.finish().is_ok());
+    }
+
+    #[test]
+    fn report_all_undefined_variables() {
+        let variables = variables();
+        let mut renderer = Renderer::new(&variables);
+
+        renderer.render("{{scheme}}://{{host}}");
+        renderer.render("X-Api-Key:{{api_key}}");
+
+        assert_eq!(
+            renderer.finish().unwrap_err().to_string(),
+            "Undefined variables: api_key, scheme"
+        );
+    }
+}
@@ -0,0 +1,221 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use http::Version;
+use reqwest::{redirect, Certificate, ClientBuilder, Proxy};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::secret::expand_home;
+
+/// The maximum number of redirects followed when none is configured
+pub const DEFAULT_MAX_REDIRECTS: usize = 10;
+
+/// Preferences for how requests are sent
+///
+/// Each setting is taken from the first source that sets it, in order of
+/// precedence: command line flags, the session, the config file, and finally
+/// the built-in default.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Transport {
+    /// The timeout for the whole request, in seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<f64>,
+
+    /// The timeout for connecting to the server, in seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connect_timeout: Option<f64>,
+
+    /// The maximum number of redirects to follow
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_redirects: Option<usize>,
+
+    /// The HTTP version to use
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http_version: Option<HttpVersion>,
+
+    /// The URL of a proxy to send requests through
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+
+    /// TLS options
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls: Option<Tls>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Tls {
+    /// Whether to skip verification of the server's certificate
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub insecure: Option<bool>,
+
+    /// The path to a PEM file of additional CA certificates to trust
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ca_cert: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, ValueEnum)]
+pub enum HttpVersion {
+    #[serde(rename = "1.0")]
+    #[value(name = "1.0")]
+    Http10,
+
+    #[serde(rename = "1.1")]
+    #[value(name = "1.1")]
+    Http11,
+
+    #[serde(rename = "2")]
+    #[value(name = "2")]
+    Http2,
+}
+
+impl HttpVersion {
+    pub fn as_version(&self) -> Version {
+        match self {
+            Self::Http10 => Version::HTTP_10,
+            Self::Http11 => Version::HTTP_11,
+            Self::Http2 => Version::HTTP_2,
+        }
+    }
+}
+
+impl Transport {
+    /// Fills settings that are not set from a source with lower precedence
+    pub fn or(self, other: Self) -> Self {
+        Self {
+            timeout: self.timeout.or(other.timeout),
+            connect_timeout: self.connect_timeout.or(other.connect_timeout),
+            max_redirects: self.max_redirects.or(other.max_redirects),
+            http_version: self.http_version.or(other.http_version),
+            proxy: self.proxy.or(other.proxy),
+            tls: match (self.tls, other.tls) {
+                (Some(tls), Some(other)) => Some(tls.or(other)),
+                (tls, other) => tls.or(other),
+            },
+        }
+    }
+
+    /// Returns the HTTP version of the request
+    pub fn version(&self) -> Version {
+        self.http_version
+            .map(|version| version.as_version())
+            .unwrap_or_default()
+    }
+
+    /// Creates a client builder with these settings applied
+    pub async fn client_builder(&self) -> Result<ClientBuilder> {
+        let mut builder = ClientBuilder::new().redirect(redirect::Policy::limited(
+            self.max_redirects.unwrap_or(DEFAULT_MAX_REDIRECTS),
+        ));
+
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(Duration::try_from_secs_f64(timeout)?);
+        }
+
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(Duration::try_from_secs_f64(timeout)?);
+        }
+
+        builder = match self.http_version {
+            Some(HttpVersion::Http10 | HttpVersion::Http11) => builder.http1_only(),
+            Some(HttpVersion::Http2) => builder.http2_prior_knowledge(),
+            None => builder,
+        };
+
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(Proxy::all(proxy).context("valid proxy URL")?);
+        }
+
+        if let Some(tls) = &self.tls {
+            if tls.insecure == Some(true) {
+                builder = builder.danger_accept_invalid_certs(true);
+            }
+
+            if let Some(path) = &tls.ca_cert {
+                let pem = fs::read(expand_home(path)?)
+                    .await
+                    .with_context(|| format!("read CA certificate {}", path))?;
+
+                for cert in Certificate::from_pem_bundle(&pem).context("parse CA certificate")? {
+                    builder = builder.add_root_certificate(cert);
+                }
+            }
+        }
+
+        Ok(builder)
+    }
+}
+
+impl Tls {
+    fn or(self, other: Self) -> Self {
+        Self {
+            insecure: self.insecure.or(other.insecure),
+            ca_cert: self.ca_cert.or(other.ca_cert),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefer_higher_precedence_settings() {
+        let cli = Transport {
+            timeout: Some(1.0),
+            tls: Some(Tls {
+                insecure: Some(true),
+                ca_cert: None,
+            }),
+            ..Default::default()
+        };
+
+        let session = Transport {
+            timeout: Some(2.0),
+            max_redirects: Some(3),
+            tls: Some(Tls {
+                insecure: Some(false),
+                ca_cert: Some("ca.pem".to_string()),
+            }),
+            ..Default::default()
+        };
+
+        let config = Transport {
+            max_redirects: Some(4),
+            proxy: Some("http://proxy:8080".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            cli.or(session).or(config),
+            Transport {
+                timeout: Some(1.0),
+                connect_timeout: None,
+                max_redirects: Some(3),
+                http_version: None,
+                proxy: Some("http://proxy:8080".to_string()),
+                tls: Some(Tls {
+                    insecure: Some(true),
+                    ca_cert: Some("ca.pem".to_string()),
+                }),
+            }
+        );
+    }
+
+    #[test]
+    fn override_insecure_session() {
+        let tls = |insecure| Transport {
+            tls: Some(Tls {
+                insecure: Some(insecure),
+                ca_cert: None,
+            }),
+            ..Default::default()
+        };
+
+        // --no-insecure sets Some(false), which takes precedence.
+        assert_eq!(tls(false).or(tls(true)), tls(false));
+    }
+}
@@ -0,0 +1,57 @@
+use std::{fmt, str::FromStr};
+
+use colored::Colorize;
+use http::{HeaderName, HeaderValue};
+
+/// A problem found while validating a config or session file, or building a
+/// request
+#[derive(Debug, PartialEq)]
+pub enum Problem {
+    /// Something likely unintended that doesn't prevent continuing
+    Warning(String),
+
+    /// Something that needs fixing
+    Error(String),
+}
+
+impl Problem {
+    /// Returns whether the problem is an error rather than a warning
+    pub fn is_error(&self) -> bool {
+        matches!(self, Self::Error(_))
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Warning(message) => write!(f, "{} {}", "warning:".yellow(), message),
+            Self::Error(message) => write!(f, "{} {}", "error:".red(), message),
+        }
+    }
+}
+
+/// Checks that a header name is valid, describing where it was set
+pub fn check_header_name(name: &str, location: &str) -> Option<Problem> {
+    HeaderName::from_str(name)
+        .err()
+        .map(|_| Problem::Error(format!("{}: invalid header name {:?}", location, name)))
+}
+
+/// Checks that a header value is valid, describing where it was set
+pub fn check_header_value(value: &str, location: &str) -> Option<Problem> {
+    HeaderValue::from_str(value)
+        .err()
+        .map(|_| Problem::Error(format!("{}: invalid header value {:?}", location, value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_headers() {
+        assert_eq!(check_header_name("X-Api-Key", "test"), None);
+        assert!(check_header_name("X Api Key", "test").is_some());
+        assert!(check_header_value("line\nbreak", "test").is_some());
+    }
+}